@@ -1,4 +1,6 @@
-
+-- The schema the site started with, as kept in db-migration/whoknows.tables.sql.
+-- Databases created from that file have no migrations table; those whose tables match
+-- it are adopted as this version.
 
 CREATE TABLE users (
   id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -12,4 +14,4 @@ CREATE TABLE pages (
     language TEXT NOT NULL CHECK(language IN ('en', 'da')) DEFAULT 'en', -- How you define ENUM type in SQLite
     last_updated TIMESTAMP,
     content TEXT NOT NULL
-);
\ No newline at end of file
+);
@@ -0,0 +1,23 @@
+-- Drops the tables 0002 created, children before the tables they reference, and
+-- rebuilds users without its role. Triggers and indexes go with their tables.
+DROP TABLE invites;
+DROP TABLE user_identities;
+DROP TABLE auth_events;
+DROP TABLE search_history;
+DROP TABLE email_verifications;
+DROP TABLE sessions;
+DROP TABLE api_tokens;
+
+CREATE TABLE users_without_role (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  username TEXT NOT NULL UNIQUE,
+  email TEXT NOT NULL UNIQUE,
+  password TEXT NOT NULL
+);
+INSERT INTO users_without_role (id, username, email, password)
+    SELECT id, username, email, password FROM users;
+DROP TABLE users;
+ALTER TABLE users_without_role RENAME TO users;
+
+DROP TABLE role_permissions;
+DROP TABLE roles;
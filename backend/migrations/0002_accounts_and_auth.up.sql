@@ -0,0 +1,146 @@
+-- Roles, API tokens, stored sessions, email verification, search history, the
+-- authentication audit log, OIDC identities and invites.
+
+CREATE TABLE roles (
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT ''
+);
+CREATE TABLE role_permissions (
+    role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+INSERT INTO roles (name, description) VALUES
+    ('admin', 'Full access, including user management'),
+    ('user', 'Regular account');
+INSERT INTO role_permissions (role, permission) VALUES
+    ('admin', 'search:read'),
+    ('admin', 'tokens:manage'),
+    ('admin', 'account:manage'),
+    ('admin', 'users:manage'),
+    ('admin', 'audit:read'),
+    ('admin', 'system:read'),
+    ('user', 'search:read'),
+    ('user', 'tokens:manage'),
+    ('user', 'account:manage');
+
+-- SQLite can't add a column that references another table with a default, so users is
+-- rebuilt with its role. No other table references users yet.
+CREATE TABLE users_with_role (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  username TEXT NOT NULL UNIQUE,
+  email TEXT NOT NULL UNIQUE,
+  password TEXT NOT NULL,
+  role TEXT NOT NULL DEFAULT 'user' REFERENCES roles(name)
+);
+INSERT INTO users_with_role (id, username, email, password)
+    SELECT id, username, email, password FROM users;
+DROP TABLE users;
+ALTER TABLE users_with_role RENAME TO users;
+
+CREATE TABLE api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE, -- SHA-256 hex of the token, the plaintext is never stored
+    token_prefix TEXT NOT NULL,      -- First characters of the token, to help users tell them apart
+    scopes TEXT NOT NULL DEFAULT '', -- Space separated, e.g. 'search:read tokens:manage'
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    expires_at TIMESTAMP,
+    revoked_at TIMESTAMP
+);
+CREATE INDEX idx_api_tokens_user_id ON api_tokens(user_id);
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,              -- Public identifier used by the session endpoints
+    key_hash TEXT NOT NULL UNIQUE,    -- SHA-256 hex of the session key held in the cookie
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    state TEXT NOT NULL,              -- JSON encoded session state
+    ip TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,          -- Idle expiry, pushed forward on every request
+    absolute_expires_at TIMESTAMP NOT NULL  -- Hard limit counted from login
+);
+CREATE INDEX idx_sessions_user_id ON sessions(user_id);
+
+CREATE TABLE email_verifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE, -- SHA-256 hex of the token mailed to the new address
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE search_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    query TEXT NOT NULL,
+    language TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_search_history_user_id ON search_history(user_id);
+
+-- Append-only: the triggers below reject deletes, and updates other than the
+-- anonymisation done when a user is erased (identifying columns set to NULL).
+CREATE TABLE auth_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    username TEXT,                    -- As typed at login, also kept for unknown usernames
+    event_type TEXT NOT NULL,         -- e.g. 'login_succeeded', 'login_failed', 'token_created'
+    ip TEXT,
+    user_agent TEXT,
+    details TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_auth_events_user_id ON auth_events(user_id);
+CREATE INDEX idx_auth_events_username_type ON auth_events(username, event_type, created_at);
+CREATE INDEX idx_auth_events_created_at ON auth_events(created_at);
+
+CREATE TRIGGER auth_events_no_delete
+BEFORE DELETE ON auth_events
+BEGIN
+    SELECT RAISE(ABORT, 'auth_events is append-only');
+END;
+
+CREATE TRIGGER auth_events_anonymise_only
+BEFORE UPDATE ON auth_events
+WHEN NEW.id IS NOT OLD.id
+  OR NEW.event_type IS NOT OLD.event_type
+  OR NEW.details IS NOT OLD.details
+  OR NEW.created_at IS NOT OLD.created_at
+  OR (NEW.user_id IS NOT OLD.user_id AND NEW.user_id IS NOT NULL)
+  OR (NEW.username IS NOT OLD.username AND NEW.username IS NOT NULL)
+  OR (NEW.ip IS NOT OLD.ip AND NEW.ip IS NOT NULL)
+  OR (NEW.user_agent IS NOT OLD.user_agent AND NEW.user_agent IS NOT NULL)
+BEGIN
+    SELECT RAISE(ABORT, 'auth_events only allows anonymisation');
+END;
+
+CREATE TABLE user_identities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    issuer TEXT NOT NULL,             -- OIDC provider issuer URL
+    subject TEXT NOT NULL,            -- `sub` claim, stable per provider
+    email TEXT,                       -- Email the provider reported at the last login
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (issuer, subject)
+);
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
+
+CREATE TABLE invites (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code_hash TEXT NOT NULL UNIQUE,   -- SHA-256 hex of the invite code
+    code_prefix TEXT NOT NULL,        -- First characters of the code, to tell invites apart
+    note TEXT NOT NULL DEFAULT '',
+    max_uses INTEGER NOT NULL DEFAULT 1 CHECK (max_uses >= 1),
+    use_count INTEGER NOT NULL DEFAULT 0,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
@@ -0,0 +1,2 @@
+DROP TABLE city_names;
+DROP TABLE cities;
@@ -0,0 +1,19 @@
+-- Local geocoding data for the weather city lookup, filled by
+-- scripts/import_geonames.py.
+
+CREATE TABLE cities (
+    id INTEGER PRIMARY KEY,           -- GeoNames geonameid
+    name TEXT NOT NULL,
+    country_code TEXT NOT NULL,       -- ISO 3166-1 alpha-2, e.g. 'DK'
+    admin1_code TEXT,                 -- GeoNames first-level division, e.g. '17' (Capital Region)
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    population INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE city_names (
+    search_name TEXT NOT NULL,        -- Folded name or alternate name, see geocoding::fold
+    city_id INTEGER NOT NULL REFERENCES cities(id) ON DELETE CASCADE,
+    PRIMARY KEY (search_name, city_id)
+) WITHOUT ROWID;
+CREATE INDEX idx_city_names_city_id ON city_names(city_id);
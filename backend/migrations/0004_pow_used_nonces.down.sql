@@ -0,0 +1 @@
+DROP TABLE pow_used_nonces;
@@ -0,0 +1,8 @@
+-- Solved proof-of-work challenges by nonce, so a solution works only once on every
+-- instance. Rows are deleted once their challenge has expired.
+
+CREATE TABLE pow_used_nonces (
+    nonce TEXT PRIMARY KEY,
+    expires_at INTEGER NOT NULL
+);
+CREATE INDEX idx_pow_used_nonces_expires_at ON pow_used_nonces(expires_at);
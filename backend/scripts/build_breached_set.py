@@ -0,0 +1,43 @@
+"""
+   Builds the breached-password prefix set read by the backend (BREACHED_PASSWORDS_FILE).
+   - input: one plaintext password per line, or SHA-1 hex lines as in the
+     Have I Been Pwned download ('HASH' or 'HASH:COUNT')
+   - output: sorted, de-duplicated big-endian 8-byte prefixes of each SHA-1
+   running:
+   'python scripts/build_breached_set.py <input.txt> <output.bin> [--sha1]'
+"""
+import sys
+import struct
+import hashlib
+
+
+def prefix_of_plaintext(line: str) -> int:
+   return int.from_bytes(hashlib.sha1(line.encode("utf-8")).digest()[:8], "big")
+
+
+def prefix_of_sha1_hex(line: str) -> int:
+   return int(line.split(":", 1)[0][:16], 16)
+
+
+def build(input_path: str, output_path: str, is_sha1: bool) -> int:
+   to_prefix = prefix_of_sha1_hex if is_sha1 else prefix_of_plaintext
+   prefixes = set()
+   with open(input_path, encoding="utf-8", errors="ignore") as source:
+      for line in source:
+         line = line.rstrip("\r\n")
+         if line:
+            prefixes.add(to_prefix(line))
+
+   with open(output_path, "wb") as target:
+      for prefix in sorted(prefixes):
+         target.write(struct.pack(">Q", prefix))
+   return len(prefixes)
+
+
+if __name__ == "__main__":
+   args = [arg for arg in sys.argv[1:] if not arg.startswith("--")]
+   if len(args) != 2:
+      print(__doc__)
+      sys.exit(1)
+   count = build(args[0], args[1], "--sha1" in sys.argv)
+   print(f"Wrote {count} prefixes to {args[1]}")
@@ -0,0 +1,101 @@
+"""
+   Imports a GeoNames cities dump into the cities and city_names tables used by
+   the weather city lookup (src/geocoding.rs).
+   - input: a tab-separated GeoNames file such as cities15000.txt or DK.txt from
+     https://download.geonames.org/export/dump/
+   - only populated places (feature class P) are imported; rows already in the
+     database are replaced, so re-running with a newer dump updates them
+   running:
+   'python scripts/import_geonames.py <cities.txt> <whoknows.db> [--country=DK,SE] [--replace]'
+   --country  only import these ISO country codes
+   --replace  delete all existing cities first
+"""
+import sqlite3
+import sys
+
+# Must match geocoding::fold in src/geocoding.rs.
+FOLD = {
+   "æ": "ae", "ä": "ae",
+   "ø": "oe", "ö": "oe", "œ": "oe",
+   "å": "aa",
+   "ü": "ue",
+   "ß": "ss",
+   "þ": "th",
+   "á": "a", "à": "a", "â": "a", "ã": "a",
+   "é": "e", "è": "e", "ê": "e", "ë": "e",
+   "í": "i", "ì": "i", "î": "i", "ï": "i",
+   "ó": "o", "ò": "o", "ô": "o", "õ": "o",
+   "ú": "u", "ù": "u", "û": "u",
+   "ý": "y", "ÿ": "y",
+   "ç": "c",
+   "ñ": "n",
+   "ð": "d",
+}
+
+
+def fold(name: str) -> str:
+   folded = []
+   for c in name.lower():
+      if "a" <= c <= "z" or "0" <= c <= "9":
+         folded.append(c)
+      else:
+         folded.append(FOLD.get(c, ""))
+   return "".join(folded)
+
+
+def rows(input_path: str, countries: set):
+   with open(input_path, encoding="utf-8") as source:
+      for line in source:
+         fields = line.rstrip("\r\n").split("\t")
+         if len(fields) < 15 or fields[6] != "P":
+            continue
+         if countries and fields[8] not in countries:
+            continue
+         yield fields
+
+
+def import_cities(input_path: str, db_path: str, countries: set, replace: bool) -> int:
+   db = sqlite3.connect(db_path)
+   db.execute("PRAGMA foreign_keys = ON")
+   count = 0
+   with db:
+      if replace:
+         db.execute("DELETE FROM cities")
+      for fields in rows(input_path, countries):
+         geoname_id = int(fields[0])
+         db.execute("DELETE FROM cities WHERE id = ?", (geoname_id,))
+         db.execute(
+            "INSERT INTO cities (id, name, country_code, admin1_code, latitude, longitude, population)"
+            " VALUES (?, ?, ?, ?, ?, ?, ?)",
+            (
+               geoname_id,
+               fields[1],
+               fields[8],
+               fields[10] or None,
+               float(fields[4]),
+               float(fields[5]),
+               int(fields[14] or 0),
+            ),
+         )
+         names = {fold(fields[1]), fold(fields[2])}
+         names.update(fold(name) for name in fields[3].split(",") if name)
+         db.executemany(
+            "INSERT INTO city_names (search_name, city_id) VALUES (?, ?)",
+            [(name, geoname_id) for name in names if name],
+         )
+         count += 1
+   db.close()
+   return count
+
+
+if __name__ == "__main__":
+   args = [arg for arg in sys.argv[1:] if not arg.startswith("--")]
+   if len(args) != 2:
+      print(__doc__)
+      sys.exit(1)
+   countries = set()
+   for arg in sys.argv[1:]:
+      if arg.startswith("--country="):
+         countries.update(code.strip().upper() for code in arg.split("=", 1)[1].split(",") if code.strip())
+   count = import_cities(args[0], args[1], countries, "--replace" in sys.argv)
+   print(f"Imported {count} cities into {args[1]}")
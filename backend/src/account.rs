@@ -0,0 +1,526 @@
+// backend/src/account.rs
+// --- Account Self-Service ---
+// Lets the logged in user see who they are, change their password or email address,
+// and delete their account. Every change re-checks the current password.
+
+use actix_session::Session;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use sqlx::{FromRow, SqlitePool};
+use std::sync::OnceLock;
+
+use crate::audit::{self, AuthEvent, ClientInfo};
+use crate::auth::AuthenticatedUser;
+use crate::password::{hash_password, verify_password};
+use crate::policy;
+use crate::rbac::{RequirePermission, ACCOUNT_MANAGE, ROLE_ADMIN};
+use crate::sessions::SESSION_ID_STATE_KEY;
+use crate::tokens::hash_token;
+use crate::{gdpr, mail};
+
+const EMAIL_VERIFICATION_TTL: &str = "+24 hours";
+
+static PUBLIC_BASE_URL: OnceLock<String> = OnceLock::new();
+
+#[derive(FromRow)]
+struct AccountRow {
+    email: String,
+    password: String,
+}
+
+#[derive(FromRow)]
+struct PendingEmailRow {
+    user_id: i64,
+    new_email: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChangePasswordForm {
+    current_password: String,
+    new_password: String,
+    new_password2: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChangeEmailForm {
+    password: String,
+    new_email: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct VerifyEmailQuery {
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeleteAccountForm {
+    password: String,
+}
+
+// --- Helper Functions ---
+/// Sets the public address from the validated configuration; must run before the
+/// first mail is sent.
+pub fn init(base_url: &str) -> Result<(), String> {
+    PUBLIC_BASE_URL
+        .set(base_url.trim_end_matches('/').to_string())
+        .map_err(|_| "Public base URL already initialised".to_string())
+}
+
+/// Public address of the site, for links that leave the app (mails, redirects).
+pub fn public_base_url() -> String {
+    PUBLIC_BASE_URL
+        .get_or_init(|| whoknows_config::DEV_PUBLIC_BASE_URL.to_string())
+        .clone()
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Loads the caller's account row and checks `password` against it.
+async fn reauthenticate(
+    pool: &SqlitePool,
+    user: &AuthenticatedUser,
+    password: &str,
+) -> Result<AccountRow, HttpResponse> {
+    let account =
+        match sqlx::query_as::<_, AccountRow>("SELECT email, password FROM users WHERE id = ?")
+            .bind(user.id)
+            .fetch_one(pool)
+            .await
+        {
+            Ok(account) => account,
+            Err(e) => {
+                log::error!(
+                    "Failed to load account for user '{}': {:?}",
+                    user.username,
+                    e
+                );
+                return Err(HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error loading account"})));
+            }
+        };
+
+    match verify_password(&account.password, password) {
+        Ok(true) => Ok(account),
+        Ok(false) => {
+            log::warn!("Re-authentication failed for user '{}'.", user.username);
+            Err(HttpResponse::Forbidden()
+                .json(serde_json::json!({"error": "Current password is incorrect"})))
+        }
+        Err(e) => {
+            log::error!(
+                "Password verification failed for user '{}': {:?}",
+                user.username,
+                e
+            );
+            Err(HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Password verification failed"})))
+        }
+    }
+}
+
+// --- Handlers ---
+
+#[get("/api/me")]
+async fn get_me(pool: web::Data<SqlitePool>, user: AuthenticatedUser) -> impl Responder {
+    match sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = ?")
+        .bind(user.id)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(email) => HttpResponse::Ok().json(serde_json::json!({
+            "id": user.id,
+            "username": user.username,
+            "email": email,
+            "role": user.role,
+            "permissions": user.permissions,
+            "auth_method": format!("{:?}", user.method).to_lowercase()
+        })),
+        Err(e) => {
+            log::error!(
+                "Failed to load account for user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error loading account"}))
+        }
+    }
+}
+
+#[post("/api/me/password", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn change_password(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    session: Session,
+    client: ClientInfo,
+    payload: web::Json<ChangePasswordForm>,
+) -> impl Responder {
+    let form = payload.into_inner();
+
+    if form.new_password.is_empty() {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Password cannot be empty"}));
+    }
+    if form.new_password != form.new_password2 {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Passwords do not match"}));
+    }
+
+    let account = match reauthenticate(pool.get_ref(), &user, &form.current_password).await {
+        Ok(account) => account,
+        Err(response) => return response,
+    };
+
+    if let Err(violation) = policy::check(&form.new_password, &user.username, &account.email).await
+    {
+        return violation.to_response();
+    }
+
+    let hashed_password = match hash_password(&form.new_password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::error!("Password hashing failed: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to process password"}));
+        }
+    };
+
+    if let Err(e) = sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+        .bind(&hashed_password)
+        .bind(user.id)
+        .execute(pool.get_ref())
+        .await
+    {
+        log::error!(
+            "Failed to update password for user '{}': {:?}",
+            user.username,
+            e
+        );
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": "Database error changing password"}));
+    }
+
+    // Sign out every other device; whoever knew the old password shouldn't stay logged in.
+    let current_session = session.get::<String>(SESSION_ID_STATE_KEY).ok().flatten();
+    if let Err(e) = sqlx::query("DELETE FROM sessions WHERE user_id = ? AND id IS NOT ?")
+        .bind(user.id)
+        .bind(current_session)
+        .execute(pool.get_ref())
+        .await
+    {
+        log::error!(
+            "Failed to revoke sessions for user '{}': {:?}",
+            user.username,
+            e
+        );
+    }
+
+    // The password is changed either way, so a lost notice is only logged.
+    if let Err(e) = mail::send(
+        &account.email,
+        "Your password was changed",
+        "The password for your WhoKnows account was just changed. \
+         If this wasn't you, contact an administrator right away.",
+    )
+    .await
+    {
+        log::error!(
+            "Failed to send password change notice to user '{}': {}",
+            user.username,
+            e
+        );
+    }
+    audit::record(
+        pool.get_ref(),
+        &client,
+        AuthEvent::PasswordChanged,
+        Some(user.id),
+        Some(&user.username),
+        None,
+    )
+    .await;
+    log::info!("User '{}' changed their password.", user.username);
+    HttpResponse::Ok().json(serde_json::json!({"message": "Password changed"}))
+}
+
+#[post("/api/me/email", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn change_email(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+    payload: web::Json<ChangeEmailForm>,
+) -> impl Responder {
+    let form = payload.into_inner();
+    let new_email = form.new_email.trim();
+
+    if new_email.is_empty() || !new_email.contains('@') {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Invalid email address"}));
+    }
+
+    let account = match reauthenticate(pool.get_ref(), &user, &form.password).await {
+        Ok(account) => account,
+        Err(response) => return response,
+    };
+
+    if account.email.eq_ignore_ascii_case(new_email) {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "That is already your email address"}));
+    }
+
+    match sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE email = ?")
+        .bind(new_email)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(0) => {}
+        Ok(_) => {
+            return HttpResponse::Conflict()
+                .json(serde_json::json!({"error": "Email already registered"}));
+        }
+        Err(e) => {
+            log::error!("Database error checking email availability: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error checking email"}));
+        }
+    }
+
+    let token = random_token();
+    let insert = async {
+        let mut tx = pool.begin().await?;
+        // Only the most recent request can be confirmed.
+        sqlx::query("DELETE FROM email_verifications WHERE user_id = ?")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO email_verifications (user_id, new_email, token_hash, expires_at)
+             VALUES (?, ?, ?, datetime('now', ?))",
+        )
+        .bind(user.id)
+        .bind(new_email)
+        .bind(hash_token(&token))
+        .bind(EMAIL_VERIFICATION_TTL)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    };
+    if let Err(e) = insert.await {
+        log::error!(
+            "Failed to store email verification for user '{}': {:?}",
+            user.username,
+            e
+        );
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({"error": "Database error changing email"}));
+    }
+
+    if let Err(e) = mail::send(
+        new_email,
+        "Confirm your new email address",
+        &format!(
+            "Open this link within 24 hours to confirm your new WhoKnows email address:\n{}/api/me/email/verify?token={}",
+            public_base_url(),
+            token
+        ),
+    )
+    .await
+    {
+        log::error!(
+            "Failed to send email verification for user '{}': {}",
+            user.username,
+            e
+        );
+        // Nobody can confirm it without the mail, so don't leave it pending.
+        if let Err(e) = sqlx::query("DELETE FROM email_verifications WHERE user_id = ?")
+            .bind(user.id)
+            .execute(pool.get_ref())
+            .await
+        {
+            log::error!(
+                "Failed to discard email verification for user '{}': {:?}",
+                user.username,
+                e
+            );
+        }
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Could not send the confirmation email, try again later"
+        }));
+    }
+    if let Err(e) = mail::send(
+        &account.email,
+        "Email change requested",
+        &format!(
+            "Someone asked to change the email address of your WhoKnows account to {}. \
+             If this wasn't you, change your password.",
+            new_email
+        ),
+    )
+    .await
+    {
+        log::error!(
+            "Failed to send email change notice to user '{}': {}",
+            user.username,
+            e
+        );
+    }
+
+    audit::record(
+        pool.get_ref(),
+        &client,
+        AuthEvent::EmailChangeRequested,
+        Some(user.id),
+        Some(&user.username),
+        None,
+    )
+    .await;
+    log::info!("User '{}' requested an email change.", user.username);
+    HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Check your new email address to confirm the change"
+    }))
+}
+
+#[get("/api/me/email/verify")]
+async fn verify_email(
+    pool: web::Data<SqlitePool>,
+    client: ClientInfo,
+    query: web::Query<VerifyEmailQuery>,
+) -> impl Responder {
+    let pending = match sqlx::query_as::<_, PendingEmailRow>(
+        "SELECT user_id, new_email FROM email_verifications
+         WHERE token_hash = ? AND expires_at > CURRENT_TIMESTAMP",
+    )
+    .bind(hash_token(&query.token))
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(pending)) => pending,
+        Ok(None) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "Invalid or expired verification link"}));
+        }
+        Err(e) => {
+            log::error!("Database error looking up email verification: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error verifying email"}));
+        }
+    };
+
+    let apply = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE users SET email = ? WHERE id = ?")
+            .bind(&pending.new_email)
+            .bind(pending.user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM email_verifications WHERE user_id = ?")
+            .bind(pending.user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    };
+
+    match apply.await {
+        Ok(()) => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::EmailChanged,
+                Some(pending.user_id),
+                None,
+                None,
+            )
+            .await;
+            log::info!("User {} confirmed a new email address.", pending.user_id);
+            HttpResponse::Ok().json(serde_json::json!({"message": "Email address updated"}))
+        }
+        // Someone registered the address between the request and the confirmation.
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            HttpResponse::Conflict().json(serde_json::json!({"error": "Email already registered"}))
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to apply email change for user {}: {:?}",
+                pending.user_id,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error verifying email"}))
+        }
+    }
+}
+
+#[delete("/api/me", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn delete_account(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    session: Session,
+    payload: web::Json<DeleteAccountForm>,
+) -> impl Responder {
+    if let Err(response) = reauthenticate(pool.get_ref(), &user, &payload.password).await {
+        return response;
+    }
+
+    if user.role == ROLE_ADMIN {
+        match sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE role = ?")
+            .bind(ROLE_ADMIN)
+            .fetch_one(pool.get_ref())
+            .await
+        {
+            Ok(1) => {
+                return HttpResponse::Conflict()
+                    .json(serde_json::json!({"error": "Cannot delete the last admin"}));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Failed to count admins: {:?}", e);
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error deleting account"}));
+            }
+        }
+    }
+
+    match gdpr::erase_user(pool.get_ref(), user.id).await {
+        Ok(_) => {
+            session.purge();
+            // Recorded without identity: the user's earlier events were just anonymised.
+            let anonymous = ClientInfo {
+                ip: None,
+                user_agent: None,
+            };
+            audit::record(
+                pool.get_ref(),
+                &anonymous,
+                AuthEvent::AccountErased,
+                None,
+                None,
+                Some("self-service"),
+            )
+            .await;
+            log::info!("User '{}' deleted their account.", user.username);
+            HttpResponse::Ok().json(serde_json::json!({"message": "Account deleted"}))
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to delete account of user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error deleting account"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_me)
+        .service(change_password)
+        .service(change_email)
+        .service(verify_email)
+        .service(delete_account);
+}
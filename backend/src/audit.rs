@@ -0,0 +1,362 @@
+// backend/src/audit.rs
+// --- Authentication Audit Log ---
+// Append-only record of authentication events in `auth_events`. Triggers in the schema
+// reject deletes and any update other than nulling identifying columns, which is what
+// GDPR erasure does.
+
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{get, web, FromRequest, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDateTime;
+use futures::future::{self, Ready};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+use whoknows_config::IpNet;
+
+use crate::auth::AuthenticatedUser;
+use crate::rbac::{RequirePermission, ACCOUNT_MANAGE, AUDIT_READ};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+// Lockout policy: this many failed logins for one username from one client address
+// within the window locks that address out of the username. Counted per username alone,
+// anyone could lock any account; guessing spread over addresses meets proof of work.
+pub const LOCKOUT_THRESHOLD: i64 = 5;
+pub const LOCKOUT_WINDOW: &str = "-15 minutes";
+
+static TRUSTED_PROXIES: OnceLock<Vec<IpNet>> = OnceLock::new();
+
+/// Sets the peers whose `X-Forwarded-For` is believed; call once at startup.
+pub fn init(trusted_proxies: Vec<IpNet>) -> Result<(), String> {
+    TRUSTED_PROXIES
+        .set(trusted_proxies)
+        .map_err(|_| "Trusted proxies already initialised".to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthEvent {
+    LoginSucceeded,
+    LoginFailed,
+    Lockout,
+    Logout,
+    Registered,
+    PasswordChanged,
+    EmailChangeRequested,
+    EmailChanged,
+    TokenCreated,
+    TokenRevoked,
+    SessionRevoked,
+    AllSessionsRevoked,
+    RoleChanged,
+    DataExported,
+    AccountErased,
+}
+
+impl AuthEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEvent::LoginSucceeded => "login_succeeded",
+            AuthEvent::LoginFailed => "login_failed",
+            AuthEvent::Lockout => "lockout",
+            AuthEvent::Logout => "logout",
+            AuthEvent::Registered => "registered",
+            AuthEvent::PasswordChanged => "password_changed",
+            AuthEvent::EmailChangeRequested => "email_change_requested",
+            AuthEvent::EmailChanged => "email_changed",
+            AuthEvent::TokenCreated => "token_created",
+            AuthEvent::TokenRevoked => "token_revoked",
+            AuthEvent::SessionRevoked => "session_revoked",
+            AuthEvent::AllSessionsRevoked => "all_sessions_revoked",
+            AuthEvent::RoleChanged => "role_changed",
+            AuthEvent::DataExported => "data_exported",
+            AuthEvent::AccountErased => "account_erased",
+        }
+    }
+}
+
+/// Client address and user agent of a request, for the audit log.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// The peer's address, or the client's as the last `X-Forwarded-For` entry when the
+/// peer is a trusted proxy. Earlier entries came from the client and prove nothing.
+fn client_ip(req: &HttpRequest) -> Option<String> {
+    let peer = req.peer_addr()?.ip();
+    let trusted = TRUSTED_PROXIES
+        .get()
+        .is_some_and(|proxies| proxies.iter().any(|net| net.contains(&peer)));
+    if trusted {
+        let forwarded = req
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse::<IpAddr>().ok());
+        if let Some(ip) = forwarded {
+            return Some(ip.to_string());
+        }
+    }
+    Some(peer.to_string())
+}
+
+impl ClientInfo {
+    pub fn from_http_request(req: &HttpRequest) -> Self {
+        ClientInfo {
+            ip: client_ip(req),
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+}
+
+impl FromRequest for ClientInfo {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        future::ok(ClientInfo::from_http_request(req))
+    }
+}
+
+#[derive(Serialize, FromRow, Debug)]
+pub struct AuthEventRow {
+    id: i64,
+    user_id: Option<i64>,
+    username: Option<String>,
+    event_type: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    details: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventFilter {
+    user_id: Option<i64>,
+    username: Option<String>,
+    event_type: Option<String>,
+    ip: Option<String>,
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Appends an event to the audit log. Failures are logged, never returned: a broken
+/// audit insert must not turn a successful login into an error.
+pub async fn record(
+    pool: &SqlitePool,
+    client: &ClientInfo,
+    event: AuthEvent,
+    user_id: Option<i64>,
+    username: Option<&str>,
+    details: Option<&str>,
+) {
+    if let Err(e) = sqlx::query(
+        "INSERT INTO auth_events (user_id, username, event_type, ip, user_agent, details)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(username)
+    .bind(event.as_str())
+    .bind(&client.ip)
+    .bind(&client.user_agent)
+    .bind(details)
+    .execute(pool)
+    .await
+    {
+        log::error!(
+            "Failed to record auth event '{}' for {:?}: {:?}",
+            event.as_str(),
+            username,
+            e
+        );
+    }
+}
+
+/// Number of failed logins for `username` from `ip` inside the lockout window.
+pub async fn recent_failures_from(
+    pool: &SqlitePool,
+    username: &str,
+    ip: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM auth_events
+         WHERE event_type = ? AND username = ? AND ip IS ? AND created_at > datetime('now', ?)",
+    )
+    .bind(AuthEvent::LoginFailed.as_str())
+    .bind(username)
+    .bind(ip)
+    .bind(LOCKOUT_WINDOW)
+    .fetch_one(pool)
+    .await
+}
+
+/// Number of failed logins for `username` inside the lockout window, from any address.
+pub async fn recent_failures(pool: &SqlitePool, username: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM auth_events
+         WHERE event_type = ? AND username = ? AND created_at > datetime('now', ?)",
+    )
+    .bind(AuthEvent::LoginFailed.as_str())
+    .bind(username)
+    .bind(LOCKOUT_WINDOW)
+    .fetch_one(pool)
+    .await
+}
+
+/// All events of one user, oldest first, for the GDPR export: those linked to the account
+/// and those that only carry its username, such as failed logins.
+pub async fn events_for_user(
+    pool: &SqlitePool,
+    user_id: i64,
+    username: &str,
+) -> Result<Vec<AuthEventRow>, sqlx::Error> {
+    sqlx::query_as::<_, AuthEventRow>(
+        "SELECT id, user_id, username, event_type, ip, user_agent, details, created_at
+         FROM auth_events WHERE user_id = ? OR username = ? ORDER BY id",
+    )
+    .bind(user_id)
+    .bind(username)
+    .fetch_all(pool)
+    .await
+}
+
+async fn query_events(
+    pool: &SqlitePool,
+    filter: &EventFilter,
+) -> Result<Vec<AuthEventRow>, sqlx::Error> {
+    let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, user_id, username, event_type, ip, user_agent, details, created_at
+         FROM auth_events WHERE 1 = 1",
+    );
+    if let Some(user_id) = filter.user_id {
+        query.push(" AND user_id = ").push_bind(user_id);
+    }
+    if let Some(username) = &filter.username {
+        query.push(" AND username = ").push_bind(username);
+    }
+    if let Some(event_type) = &filter.event_type {
+        query.push(" AND event_type = ").push_bind(event_type);
+    }
+    if let Some(ip) = &filter.ip {
+        query.push(" AND ip = ").push_bind(ip);
+    }
+    if let Some(since) = filter.since {
+        query.push(" AND created_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        query.push(" AND created_at < ").push_bind(until);
+    }
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    query
+        .push(" ORDER BY id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(filter.offset.unwrap_or(0).max(0));
+
+    query.build_query_as().fetch_all(pool).await
+}
+
+// --- Handlers ---
+
+#[get("/api/me/events", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn my_events(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    query: web::Query<EventFilter>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let filter = EventFilter {
+        user_id: Some(user.id),
+        username: None,
+        ip: None,
+        ..query
+    };
+
+    match query_events(pool.get_ref(), &filter).await {
+        Ok(events) => HttpResponse::Ok().json(serde_json::json!({ "events": events })),
+        Err(e) => {
+            log::error!(
+                "Failed to list auth events for user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error listing events"}))
+        }
+    }
+}
+
+#[get("/api/admin/auth-events", wrap = "RequirePermission::new(AUDIT_READ)")]
+async fn all_events(pool: web::Data<SqlitePool>, query: web::Query<EventFilter>) -> impl Responder {
+    match query_events(pool.get_ref(), &query).await {
+        Ok(events) => HttpResponse::Ok().json(serde_json::json!({ "events": events })),
+        Err(e) => {
+            log::error!("Failed to query auth events: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error listing events"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(my_events).service(all_events);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    fn client(ip: &str) -> ClientInfo {
+        ClientInfo {
+            ip: Some(ip.to_string()),
+            user_agent: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn failures_from_one_address_do_not_lock_out_another() {
+        let pool = migrations::memory_pool().await;
+        let attacker = client("203.0.113.7");
+        for _ in 0..LOCKOUT_THRESHOLD {
+            record(
+                &pool,
+                &attacker,
+                AuthEvent::LoginFailed,
+                None,
+                Some("alice"),
+                None,
+            )
+            .await;
+        }
+
+        let from_attacker = recent_failures_from(&pool, "alice", attacker.ip.as_deref())
+            .await
+            .unwrap();
+        let from_owner = recent_failures_from(&pool, "alice", Some("198.51.100.2"))
+            .await
+            .unwrap();
+        assert!(from_attacker >= LOCKOUT_THRESHOLD);
+        assert_eq!(from_owner, 0);
+        // Proof of work still counts them for the username as a whole.
+        assert_eq!(
+            recent_failures(&pool, "alice").await.unwrap(),
+            LOCKOUT_THRESHOLD
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// backend/src/auth.rs
+// --- Request Authentication ---
+// Resolves the caller of a request either from an `Authorization: Bearer` API token
+// or from the `user_id` that `post_login` stores in the cookie session.
+
+use actix_session::SessionExt;
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use sqlx::{FromRow, SqlitePool};
+
+use crate::{rbac, tokens};
+
+/// How the caller proved who they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Session,
+    Token,
+}
+
+/// The user behind the current request.
+///
+/// Use it as a handler argument to require authentication. Routes that also serve
+/// anonymous callers call `resolve_user` instead: `Option<AuthenticatedUser>` would
+/// turn an invalid token into an anonymous caller.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub id: i64,
+    pub username: String,
+    pub role: String,
+    pub method: AuthMethod,
+    /// Effective permissions: the role's permissions, narrowed to the token's scopes
+    /// when the request was authenticated with an API token.
+    pub permissions: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+#[derive(FromRow)]
+struct SessionUserRow {
+    id: i64,
+    username: String,
+    role: String,
+}
+
+/// Builds an error carrying the `{"error": ...}` JSON body used by every handler.
+pub fn json_error(status: StatusCode, message: &'static str) -> actix_web::Error {
+    InternalError::from_response(
+        message,
+        HttpResponse::build(status).json(serde_json::json!({ "error": message })),
+    )
+    .into()
+}
+
+/// Returns the raw token from an `Authorization: Bearer <token>` header, if present.
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Resolves the caller of `req`.
+///
+/// A bearer token takes precedence over the session; an invalid token is rejected
+/// outright instead of silently falling back to the cookie. Users already resolved by
+/// `rbac::RequirePermission` are taken from the request extensions.
+pub async fn resolve_user(
+    req: &HttpRequest,
+) -> Result<Option<AuthenticatedUser>, actix_web::Error> {
+    if let Some(user) = req.extensions().get::<AuthenticatedUser>() {
+        return Ok(Some(user.clone()));
+    }
+
+    let pool = match req.app_data::<web::Data<SqlitePool>>() {
+        Some(pool) => pool.clone(),
+        None => {
+            log::error!("SqlitePool missing from app data; cannot authenticate request");
+            return Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Authentication unavailable",
+            ));
+        }
+    };
+
+    if let Some(token) = bearer_token(req) {
+        return match tokens::authenticate(pool.get_ref(), &token).await {
+            Ok(Some(user)) => Ok(Some(user)),
+            Ok(None) => Err(json_error(
+                StatusCode::UNAUTHORIZED,
+                "Invalid or expired API token",
+            )),
+            Err(e) => {
+                log::error!("Database error during token authentication: {:?}", e);
+                Err(json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Authentication failed (database error)",
+                ))
+            }
+        };
+    }
+
+    let session = req.get_session();
+    let user_id = match session.get::<i64>("user_id") {
+        Ok(Some(id)) => id,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            log::warn!("Could not read user_id from session: {:?}", e);
+            return Ok(None);
+        }
+    };
+
+    let row =
+        sqlx::query_as::<_, SessionUserRow>("SELECT id, username, role FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(pool.get_ref())
+            .await;
+
+    match row {
+        Ok(Some(row)) => match rbac::role_permissions(pool.get_ref(), &row.role).await {
+            Ok(permissions) => Ok(Some(AuthenticatedUser {
+                id: row.id,
+                username: row.username,
+                role: row.role,
+                method: AuthMethod::Session,
+                permissions,
+            })),
+            Err(e) => {
+                log::error!(
+                    "Database error loading permissions for role '{}': {:?}",
+                    row.role,
+                    e
+                );
+                Err(json_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Authentication failed (database error)",
+                ))
+            }
+        },
+        Ok(None) => {
+            // The account was deleted while the session was still alive.
+            session.purge();
+            Ok(None)
+        }
+        Err(e) => {
+            log::error!("Database error resolving session user {}: {:?}", user_id, e);
+            Err(json_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Authentication failed (database error)",
+            ))
+        }
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            resolve_user(&req)
+                .await?
+                .ok_or_else(|| json_error(StatusCode::UNAUTHORIZED, "Authentication required"))
+        })
+    }
+}
@@ -0,0 +1,435 @@
+// backend/src/cli.rs
+// --- Command Line ---
+// Subcommands of the backend binary. `serve`, the default, runs the server; the others
+// are admin tasks that run against the same configuration and database as the server
+// and exit, e.g. `docker compose exec backend ./backend create-user alice a@example.com`.
+// Results go to stdout, failures to the log.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+use crate::audit::{self, AuthEvent, ClientInfo};
+use crate::migrations;
+use crate::password::hash_password;
+use crate::policy;
+use crate::rbac::{self, RoleChangeError};
+use crate::sessions::random_string;
+
+/// Length of the passwords generated when none is given.
+const GENERATED_PASSWORD_LEN: usize = 20;
+
+#[derive(Parser)]
+#[command(
+    name = "backend",
+    version,
+    about = "WhoKnows backend server and admin commands"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default)
+    Serve,
+    /// Check that the running server is ready, for the Docker HEALTHCHECK
+    Healthcheck,
+    /// Validate the configuration and print it with secrets redacted
+    CheckConfig,
+    /// Apply pending schema migrations
+    Migrate {
+        #[command(subcommand)]
+        action: Option<MigrateAction>,
+    },
+    /// Create a user, printing a generated password unless one is read from stdin
+    CreateUser {
+        username: String,
+        email: String,
+        #[arg(long, default_value = "user")]
+        role: String,
+        /// Read the password from the first line of stdin
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Change a user's role
+    SetRole { username: String, role: String },
+    /// Set a new password for a user and sign them out everywhere
+    ResetPassword {
+        username: String,
+        /// Read the password from the first line of stdin
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Load pages from a JSON Lines file (`-` for stdin), replacing pages with the same title
+    ImportPages { file: PathBuf },
+    /// Write all pages as JSON Lines to a file, or to stdout
+    ExportPages { file: Option<PathBuf> },
+    /// Rebuild the indexes and refresh the query planner's statistics
+    Reindex,
+    /// Write a consistent copy of the database to a new file; safe while the server runs
+    Backup { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+pub enum MigrateAction {
+    /// Revert every migration newer than <version>; 0 reverts them all
+    Down { version: i64 },
+}
+
+impl Command {
+    /// Commands that work on any schema, so they run before the startup schema check;
+    /// a backup is most useful right before migrating.
+    pub fn skips_schema_check(&self) -> bool {
+        matches!(self, Command::Migrate { .. } | Command::Backup { .. })
+    }
+}
+
+/// Runs an admin command against `pool`. Serving, `healthcheck` and `check-config` are
+/// handled by `main`, since they don't need the database.
+pub async fn run(command: Command, pool: &SqlitePool) -> Result<(), String> {
+    match command {
+        Command::Migrate { action: None } => migrations::prepare(pool, true).await,
+        Command::Migrate {
+            action: Some(MigrateAction::Down { version }),
+        } => migrations::undo(pool, version).await,
+        Command::CreateUser {
+            username,
+            email,
+            role,
+            password_stdin,
+        } => create_user(pool, &username, &email, &role, password_stdin).await,
+        Command::SetRole { username, role } => set_role(pool, &username, &role).await,
+        Command::ResetPassword {
+            username,
+            password_stdin,
+        } => reset_password(pool, &username, password_stdin).await,
+        Command::ImportPages { file } => import_pages(pool, &file).await,
+        Command::ExportPages { file } => export_pages(pool, file.as_deref()).await,
+        Command::Reindex => reindex(pool).await,
+        Command::Backup { file } => backup(pool, &file).await,
+        Command::Serve | Command::Healthcheck | Command::CheckConfig => {
+            Err("This command doesn't use the database".to_string())
+        }
+    }
+}
+
+fn db_error(action: &str) -> impl Fn(sqlx::Error) -> String + '_ {
+    move |e| format!("Database error {}: {}", action, e)
+}
+
+/// Audit log entries for admin commands carry no address, and this as their user agent.
+fn cli_client() -> ClientInfo {
+    ClientInfo {
+        ip: None,
+        user_agent: Some("backend cli".to_string()),
+    }
+}
+
+/// The password from stdin when asked for, otherwise a generated one.
+fn new_password(password_stdin: bool) -> Result<String, String> {
+    if !password_stdin {
+        return Ok(random_string(GENERATED_PASSWORD_LEN));
+    }
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read the password from stdin: {}", e))?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("Password cannot be empty".to_string());
+    }
+    Ok(password)
+}
+
+async fn find_user(pool: &SqlitePool, username: &str) -> Result<(i64, String), String> {
+    sqlx::query_as::<_, (i64, String)>("SELECT id, email FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_error("looking up the user"))?
+        .ok_or_else(|| format!("No user named '{}'", username))
+}
+
+// --- Users ---
+
+async fn create_user(
+    pool: &SqlitePool,
+    username: &str,
+    email: &str,
+    role: &str,
+    password_stdin: bool,
+) -> Result<(), String> {
+    let username = username.trim();
+    let email = email.trim();
+    if username.is_empty() {
+        return Err("Username cannot be empty".to_string());
+    }
+    if email.is_empty() || !email.contains('@') {
+        return Err("Invalid email address".to_string());
+    }
+    let password = new_password(password_stdin)?;
+    policy::check(&password, username, email)
+        .await
+        .map_err(|violation| violation.message())?;
+    let hashed_password =
+        hash_password(&password).map_err(|e| format!("Password hashing failed: {}", e))?;
+
+    let mut tx = pool.begin().await.map_err(db_error("creating the user"))?;
+    let known_role = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM roles WHERE name = ?")
+        .bind(role)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error("looking up the role"))?;
+    if known_role == 0 {
+        return Err(format!("Unknown role '{}'", role));
+    }
+    let existing = sqlx::query_scalar::<_, String>(
+        "SELECT username FROM users WHERE username = ? OR email = ?",
+    )
+    .bind(username)
+    .bind(email)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error("checking for an existing user"))?;
+    if let Some(existing) = existing {
+        return Err(if existing.eq_ignore_ascii_case(username) {
+            "Username already taken".to_string()
+        } else {
+            "Email already registered".to_string()
+        });
+    }
+
+    let user_id =
+        sqlx::query("INSERT INTO users (username, email, password, role) VALUES (?, ?, ?, ?)")
+            .bind(username)
+            .bind(email)
+            .bind(&hashed_password)
+            .bind(role)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error("creating the user"))?
+            .last_insert_rowid();
+    tx.commit().await.map_err(db_error("creating the user"))?;
+
+    audit::record(
+        pool,
+        &cli_client(),
+        AuthEvent::Registered,
+        Some(user_id),
+        Some(username),
+        Some(&format!(
+            "created from the command line with role '{}'",
+            role
+        )),
+    )
+    .await;
+    println!(
+        "Created user '{}' (id {}) with role '{}'.",
+        username, user_id, role
+    );
+    if !password_stdin {
+        println!("Generated password: {}", password);
+    }
+    Ok(())
+}
+
+async fn set_role(pool: &SqlitePool, username: &str, role: &str) -> Result<(), String> {
+    let (user_id, _) = find_user(pool, username).await?;
+    rbac::change_role(pool, user_id, role)
+        .await
+        .map_err(|e| match e {
+            RoleChangeError::UnknownRole => format!("Unknown role '{}'", role),
+            RoleChangeError::UserNotFound => format!("No user named '{}'", username),
+            RoleChangeError::LastAdmin => "Cannot remove the last admin".to_string(),
+            RoleChangeError::Database(e) => db_error("changing the role")(e),
+        })?;
+
+    audit::record(
+        pool,
+        &cli_client(),
+        AuthEvent::RoleChanged,
+        Some(user_id),
+        Some(username),
+        Some(&format!("set to '{}' from the command line", role)),
+    )
+    .await;
+    println!("Set the role of '{}' to '{}'.", username, role);
+    Ok(())
+}
+
+async fn reset_password(
+    pool: &SqlitePool,
+    username: &str,
+    password_stdin: bool,
+) -> Result<(), String> {
+    let (user_id, email) = find_user(pool, username).await?;
+    let password = new_password(password_stdin)?;
+    policy::check(&password, username, &email)
+        .await
+        .map_err(|violation| violation.message())?;
+    let hashed_password =
+        hash_password(&password).map_err(|e| format!("Password hashing failed: {}", e))?;
+
+    // Whoever knew the old password shouldn't stay logged in.
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(db_error("resetting the password"))?;
+    sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+        .bind(&hashed_password)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error("resetting the password"))?;
+    let revoked = sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error("revoking sessions"))?
+        .rows_affected();
+    tx.commit()
+        .await
+        .map_err(db_error("resetting the password"))?;
+
+    audit::record(
+        pool,
+        &cli_client(),
+        AuthEvent::PasswordChanged,
+        Some(user_id),
+        Some(username),
+        Some("reset from the command line"),
+    )
+    .await;
+    println!(
+        "Reset the password of '{}' and revoked {} session(s).",
+        username, revoked
+    );
+    if !password_stdin {
+        println!("Generated password: {}", password);
+    }
+    Ok(())
+}
+
+// --- Pages ---
+
+/// One line of an import or export file.
+#[derive(Serialize, Deserialize, FromRow)]
+struct PageRecord {
+    title: String,
+    url: String,
+    language: String,
+    /// Kept as stored, e.g. `2024-01-31 12:00:00`.
+    #[serde(default)]
+    last_updated: Option<String>,
+    content: String,
+}
+
+async fn import_pages(pool: &SqlitePool, file: &Path) -> Result<(), String> {
+    let reader: Box<dyn BufRead> = if file == Path::new("-") {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let opened =
+            File::open(file).map_err(|e| format!("Failed to open {}: {}", file.display(), e))?;
+        Box::new(BufReader::new(opened))
+    };
+
+    // All or nothing, so a bad line doesn't leave half an import behind.
+    let mut tx = pool.begin().await.map_err(db_error("importing pages"))?;
+    let mut imported = 0;
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|e| format!("Failed to read line {}: {}", line_number, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let page: PageRecord = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid page on line {}: {}", line_number, e))?;
+        sqlx::query(
+            "INSERT INTO pages (title, url, language, last_updated, content)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(title) DO UPDATE SET
+                 url = excluded.url,
+                 language = excluded.language,
+                 last_updated = excluded.last_updated,
+                 content = excluded.content",
+        )
+        .bind(&page.title)
+        .bind(&page.url)
+        .bind(&page.language)
+        .bind(&page.last_updated)
+        .bind(&page.content)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to import line {}: {}", line_number, e))?;
+        imported += 1;
+    }
+    tx.commit().await.map_err(db_error("importing pages"))?;
+
+    println!("Imported {} page(s).", imported);
+    Ok(())
+}
+
+async fn export_pages(pool: &SqlitePool, file: Option<&Path>) -> Result<(), String> {
+    let pages = sqlx::query_as::<_, PageRecord>(
+        "SELECT title, url, language, CAST(last_updated AS TEXT) AS last_updated, content
+         FROM pages ORDER BY title",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(db_error("reading pages"))?;
+
+    let mut writer: Box<dyn Write> = match file {
+        Some(file) => Box::new(
+            File::create(file)
+                .map_err(|e| format!("Failed to create {}: {}", file.display(), e))?,
+        ),
+        None => Box::new(io::stdout().lock()),
+    };
+    let write_failed = |e: io::Error| format!("Failed to write pages: {}", e);
+    for page in &pages {
+        let line = serde_json::to_string(page).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(write_failed)?;
+    }
+    writer.flush().map_err(write_failed)?;
+
+    // Keeps stdout clean for piping when no file is given.
+    if let Some(file) = file {
+        println!("Exported {} page(s) to {}.", pages.len(), file.display());
+    }
+    Ok(())
+}
+
+// --- Maintenance ---
+
+async fn reindex(pool: &SqlitePool) -> Result<(), String> {
+    for statement in ["REINDEX", "ANALYZE", "PRAGMA optimize"] {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .map_err(|e| format!("{} failed: {}", statement, e))?;
+    }
+    println!("Rebuilt indexes and refreshed statistics.");
+    Ok(())
+}
+
+async fn backup(pool: &SqlitePool, file: &Path) -> Result<(), String> {
+    if file.exists() {
+        return Err(format!("{} already exists", file.display()));
+    }
+    let target = file
+        .to_str()
+        .ok_or_else(|| format!("{} is not a valid UTF-8 path", file.display()))?;
+    // Reads a consistent snapshot, so the server can keep writing meanwhile.
+    sqlx::query("VACUUM INTO ?")
+        .bind(target)
+        .execute(pool)
+        .await
+        .map_err(db_error("writing the backup"))?;
+    println!("Backed up the database to {}.", file.display());
+    Ok(())
+}
@@ -0,0 +1,162 @@
+// backend/src/csrf.rs
+// --- CSRF Protection ---
+// Synchronizer tokens: `GET /api/csrf-token` stores a random token in the session and
+// returns it, and `CsrfProtection` requires it in the `X-CSRF-Token` header of every
+// unsafe request. Requests authenticated with a valid bearer token are exempt, since
+// a browser never attaches one on its own.
+use std::rc::Rc;
+
+use actix_session::{Session, SessionExt};
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{get, web, Error, HttpMessage, HttpResponse, Responder};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use futures::future::{self, LocalBoxFuture, Ready};
+
+use crate::auth::{json_error, resolve_user, AuthMethod};
+
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+/// Session state entry holding the token; `sessions::start_login` drops it.
+pub const CSRF_STATE_KEY: &str = "csrf_token";
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Compares without stopping at the first difference, so timing reveals nothing.
+pub(crate) fn tokens_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+fn has_bearer_header(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.len() > 7 && v[..7].eq_ignore_ascii_case("bearer "))
+}
+
+// --- Middleware ---
+
+/// Rejects unsafe requests without a valid CSRF token with 403. Must be wrapped
+/// inside `SessionMiddleware`.
+pub struct CsrfProtection;
+
+impl<S, B> Transform<S, ServiceRequest> for CsrfProtection
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CsrfProtectionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(CsrfProtectionMiddleware {
+            service: Rc::new(service),
+        })
+    }
+}
+
+pub struct CsrfProtectionMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfProtectionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            if is_safe_method(req.method()) {
+                let res = service.call(req).await?;
+                return Ok(res.map_into_left_body());
+            }
+
+            if has_bearer_header(&req) {
+                // Only a token that actually authenticates earns the exemption.
+                match resolve_user(req.request()).await {
+                    Ok(Some(user)) if user.method == AuthMethod::Token => {
+                        req.extensions_mut().insert(user);
+                        let res = service.call(req).await?;
+                        return Ok(res.map_into_left_body());
+                    }
+                    Ok(_) => {}
+                    Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+                }
+            }
+
+            let expected = req
+                .get_session()
+                .get::<String>(CSRF_STATE_KEY)
+                .ok()
+                .flatten();
+            let provided = req.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok());
+
+            match (expected, provided) {
+                (Some(expected), Some(provided)) if tokens_match(&expected, provided) => {
+                    let res = service.call(req).await?;
+                    Ok(res.map_into_left_body())
+                }
+                _ => {
+                    log::warn!("CSRF check failed for {} {}.", req.method(), req.path());
+                    let err = json_error(StatusCode::FORBIDDEN, "Invalid or missing CSRF token");
+                    Ok(req.error_response(err).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+// --- Handlers ---
+
+#[get("/api/csrf-token")]
+async fn get_csrf_token(session: Session) -> impl Responder {
+    let token = match session.get::<String>(CSRF_STATE_KEY) {
+        Ok(Some(token)) => token,
+        _ => {
+            let token = generate_token();
+            if let Err(e) = session.insert(CSRF_STATE_KEY, &token) {
+                log::error!("Failed to store CSRF token in session: {:?}", e);
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Failed to issue CSRF token"}));
+            }
+            token
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(serde_json::json!({ "csrf_token": token, "header": CSRF_HEADER }))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_csrf_token);
+}
@@ -0,0 +1,191 @@
+// backend/src/db_tracing.rs
+// --- Query Spans ---
+// sqlx reports every finished statement as a `tracing` event on `sqlx::query`; this
+// layer turns those events into OpenTelemetry spans under the request that ran them.
+// SQLite statements run on a connection worker thread, which sqlx enters the caller's
+// `tracing` span on, so `QueryScope` runs each request inside a `tracing` span that
+// remembers the request's OpenTelemetry context.
+use std::time::{Duration, SystemTime};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures::future::{self, LocalBoxFuture, Ready};
+use opentelemetry::trace::{Span, SpanKind, TraceContextExt, Tracer};
+use opentelemetry::{Context as OtelContext, KeyValue};
+use tracing::field::{Field, Visit};
+use tracing::span::Id;
+use tracing::subscriber::Interest;
+use tracing::{Event, Instrument, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+const QUERY_TARGET: &str = "sqlx::query";
+const SCOPE_TARGET: &str = module_path!();
+
+/// Installs the layer as the global `tracing` subscriber. sqlx's own log output keeps
+/// going to the logger, since `tracing` is built with `log-always`.
+pub fn init() -> Result<(), String> {
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(QuerySpans))
+        .map_err(|e| format!("Failed to install query tracing: {}", e))
+}
+
+fn is_ours(metadata: &Metadata<'_>) -> bool {
+    metadata.target() == QUERY_TARGET || metadata.target() == SCOPE_TARGET
+}
+
+/// Fields sqlx records on each statement.
+#[derive(Default)]
+struct QueryFields {
+    summary: String,
+    statement: String,
+    rows_affected: Option<u64>,
+    rows_returned: Option<u64>,
+    elapsed_secs: f64,
+}
+
+impl Visit for QueryFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.statement = value.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "rows_affected" => self.rows_affected = Some(value),
+            "rows_returned" => self.rows_returned = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+struct QuerySpans;
+
+impl<S> Layer<S> for QuerySpans
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if is_ours(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        is_ours(metadata)
+    }
+
+    // First entered while the request's future is polled, where its context is current.
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if extensions.get_mut::<OtelContext>().is_none() {
+                extensions.insert(OtelContext::current());
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != QUERY_TARGET {
+            return;
+        }
+        let mut fields = QueryFields::default();
+        event.record(&mut fields);
+
+        let parent = ctx
+            .event_span(event)
+            .and_then(|span| {
+                span.scope()
+                    .find_map(|span| span.extensions().get::<OtelContext>().cloned())
+            })
+            .unwrap_or_else(OtelContext::current);
+        if !parent.has_active_span() {
+            // Startup and background queries, not part of any request.
+            return;
+        }
+
+        let end = SystemTime::now();
+        let start = end - Duration::from_secs_f64(fields.elapsed_secs);
+        let query_text = if fields.statement.is_empty() {
+            fields.summary.clone()
+        } else {
+            fields.statement
+        };
+        let mut attributes = vec![
+            KeyValue::new("db.system.name", "sqlite"),
+            KeyValue::new("db.query.text", query_text),
+        ];
+        if let Some(rows) = fields.rows_affected {
+            attributes.push(KeyValue::new("db.rows_affected", rows as i64));
+        }
+        if let Some(rows) = fields.rows_returned {
+            attributes.push(KeyValue::new("db.response.returned_rows", rows as i64));
+        }
+
+        let tracer = whoknows_telemetry::tracer();
+        let mut span = tracer
+            .span_builder(fields.summary)
+            .with_kind(SpanKind::Client)
+            .with_start_time(start)
+            .with_attributes(attributes)
+            .start_with_context(&tracer, &parent);
+        span.end_with_timestamp(end);
+    }
+}
+
+// --- Request Scope Middleware ---
+
+/// Runs the request inside a `tracing` span for its queries to find; must sit inside
+/// `whoknows_telemetry::TraceRequests`.
+pub struct QueryScope;
+
+impl<S, B> Transform<S, ServiceRequest> for QueryScope
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = QueryScopeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(QueryScopeMiddleware { service })
+    }
+}
+
+pub struct QueryScopeMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for QueryScopeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let scope = tracing::debug_span!(target: SCOPE_TARGET, "request");
+        Box::pin(self.service.call(req).instrument(scope))
+    }
+}
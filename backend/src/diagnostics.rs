@@ -0,0 +1,97 @@
+// backend/src/diagnostics.rs
+// --- Diagnostics ---
+// Admin-only view of the running server: the effective configuration with secrets
+// redacted, build version, uptime, database size, connection pool usage and the
+// applied migration version.
+use std::sync::OnceLock;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use whoknows_config::Config;
+
+use crate::migrations;
+use crate::rbac::{RequirePermission, SYSTEM_READ};
+
+static STARTED_AT: OnceLock<DateTime<Utc>> = OnceLock::new();
+
+#[derive(Serialize, Debug)]
+struct DatabaseStats {
+    size_bytes: i64,
+    page_size: i64,
+    page_count: i64,
+    free_pages: i64,
+    /// Latest applied migration; `None` for an empty database.
+    migration_version: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+struct PoolStats {
+    size: u32,
+    idle: usize,
+    max_connections: u32,
+}
+
+/// Records the start time uptime is measured from; call once at startup.
+pub fn init() {
+    started_at();
+}
+
+fn started_at() -> DateTime<Utc> {
+    *STARTED_AT.get_or_init(Utc::now)
+}
+
+async fn pragma(pool: &SqlitePool, name: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(&format!("PRAGMA {}", name))
+        .fetch_one(pool)
+        .await
+}
+
+async fn database_stats(pool: &SqlitePool) -> Result<DatabaseStats, sqlx::Error> {
+    let page_size = pragma(pool, "page_size").await?;
+    let page_count = pragma(pool, "page_count").await?;
+    Ok(DatabaseStats {
+        size_bytes: page_size * page_count,
+        page_size,
+        page_count,
+        free_pages: pragma(pool, "freelist_count").await?,
+        migration_version: migrations::schema_version(pool).await?,
+    })
+}
+
+// --- Handlers ---
+
+#[get("/api/admin/diagnostics", wrap = "RequirePermission::new(SYSTEM_READ)")]
+async fn get_diagnostics(
+    pool: web::Data<SqlitePool>,
+    settings: web::Data<Config>,
+) -> impl Responder {
+    let database = match database_stats(pool.get_ref()).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            log::error!("Failed to read database statistics: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error reading diagnostics"}));
+        }
+    };
+    let pool_stats = PoolStats {
+        size: pool.size(),
+        idle: pool.num_idle(),
+        max_connections: pool.options().get_max_connections(),
+    };
+    let started_at = started_at();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "build_version": settings.backend.build_version,
+        "started_at": started_at,
+        "uptime_secs": (Utc::now() - started_at).num_seconds(),
+        "database": database,
+        "pool": pool_stats,
+        "config": settings.get_ref(),
+    }))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_diagnostics);
+}
@@ -0,0 +1,331 @@
+// backend/src/gdpr.rs
+// --- Data Export and Erasure ---
+// GDPR article 15/20 export of everything stored about a user, and article 17 erasure.
+// When a table holding personal data is added, add it to both `export_user` and
+// `erase_user`.
+
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{FromRow, SqlitePool};
+
+use crate::audit::{self, AuthEvent, AuthEventRow, ClientInfo};
+use crate::auth::AuthenticatedUser;
+use crate::rbac::{RequirePermission, ACCOUNT_MANAGE, ROLE_ADMIN, USERS_MANAGE};
+
+#[derive(Serialize, FromRow, Debug)]
+struct ExportedUser {
+    id: i64,
+    username: String,
+    email: String,
+    role: String,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct ExportedSearch {
+    query: String,
+    language: String,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct ExportedSession {
+    id: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    created_at: NaiveDateTime,
+    last_seen_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct ExportedToken {
+    name: String,
+    token_prefix: String,
+    scopes: String,
+    created_at: NaiveDateTime,
+    last_used_at: Option<NaiveDateTime>,
+    expires_at: Option<NaiveDateTime>,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct ExportedEmailChange {
+    new_email: String,
+    created_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct ExportedIdentity {
+    issuer: String,
+    subject: String,
+    email: Option<String>,
+    created_at: NaiveDateTime,
+    last_login_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug)]
+struct UserExport {
+    exported_at: NaiveDateTime,
+    user: ExportedUser,
+    search_history: Vec<ExportedSearch>,
+    sessions: Vec<ExportedSession>,
+    api_tokens: Vec<ExportedToken>,
+    pending_email_changes: Vec<ExportedEmailChange>,
+    linked_identities: Vec<ExportedIdentity>,
+    auth_events: Vec<AuthEventRow>,
+}
+
+/// Collects everything stored about a user. The password hash is deliberately left out.
+async fn export_user(pool: &SqlitePool, user_id: i64) -> Result<UserExport, sqlx::Error> {
+    let user = sqlx::query_as::<_, ExportedUser>(
+        "SELECT id, username, email, role FROM users WHERE id = ?",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    let search_history = sqlx::query_as::<_, ExportedSearch>(
+        "SELECT query, language, created_at FROM search_history
+         WHERE user_id = ? ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let sessions = sqlx::query_as::<_, ExportedSession>(
+        "SELECT id, ip, user_agent, created_at, last_seen_at, expires_at FROM sessions
+         WHERE user_id = ? ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let api_tokens = sqlx::query_as::<_, ExportedToken>(
+        "SELECT name, token_prefix, scopes, created_at, last_used_at, expires_at, revoked_at
+         FROM api_tokens WHERE user_id = ? ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let pending_email_changes = sqlx::query_as::<_, ExportedEmailChange>(
+        "SELECT new_email, created_at, expires_at FROM email_verifications
+         WHERE user_id = ? ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let linked_identities = sqlx::query_as::<_, ExportedIdentity>(
+        "SELECT issuer, subject, email, created_at, last_login_at FROM user_identities
+         WHERE user_id = ? ORDER BY created_at",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let auth_events = audit::events_for_user(pool, user_id, &user.username).await?;
+
+    Ok(UserExport {
+        exported_at: chrono::Utc::now().naive_utc(),
+        user,
+        search_history,
+        sessions,
+        api_tokens,
+        pending_email_changes,
+        linked_identities,
+        auth_events,
+    })
+}
+
+/// Removes a user and all personal data linked to them, in one transaction. The audit
+/// log is append-only, so the user's events are anonymised instead of deleted, including
+/// those that only carry their username, such as failed logins.
+///
+/// Returns `Ok(false)` when there is no such user.
+pub async fn erase_user(pool: &SqlitePool, user_id: i64) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let Some(username) = username else {
+        return Ok(false);
+    };
+    sqlx::query(
+        "UPDATE auth_events SET user_id = NULL, username = NULL, ip = NULL, user_agent = NULL
+         WHERE user_id = ? OR username = ?",
+    )
+    .bind(user_id)
+    .bind(&username)
+    .execute(&mut *tx)
+    .await?;
+
+    for statement in [
+        "DELETE FROM search_history WHERE user_id = ?",
+        "DELETE FROM sessions WHERE user_id = ?",
+        "DELETE FROM api_tokens WHERE user_id = ?",
+        "DELETE FROM email_verifications WHERE user_id = ?",
+        "DELETE FROM user_identities WHERE user_id = ?",
+    ] {
+        sqlx::query(statement)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let deleted = sqlx::query("DELETE FROM users WHERE id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    tx.commit().await?;
+    Ok(deleted == 1)
+}
+
+fn export_response(export: &UserExport) -> HttpResponse {
+    let filename = format!("whoknows-export-{}.json", export.user.id);
+    HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .json(export)
+}
+
+// --- Handlers ---
+
+#[get("/api/me/export", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn export_me(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+) -> impl Responder {
+    match export_user(pool.get_ref(), user.id).await {
+        Ok(export) => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::DataExported,
+                Some(user.id),
+                Some(&user.username),
+                None,
+            )
+            .await;
+            log::info!("User '{}' exported their data.", user.username);
+            export_response(&export)
+        }
+        Err(e) => {
+            log::error!("Failed to export data of user '{}': {:?}", user.username, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error exporting data"}))
+        }
+    }
+}
+
+#[get(
+    "/api/admin/users/{id}/export",
+    wrap = "RequirePermission::new(USERS_MANAGE)"
+)]
+async fn export_user_as_admin(
+    pool: web::Data<SqlitePool>,
+    admin: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<i64>,
+) -> impl Responder {
+    let user_id = path.into_inner();
+    match export_user(pool.get_ref(), user_id).await {
+        Ok(export) => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::DataExported,
+                Some(user_id),
+                None,
+                Some(&format!("by user {}", admin.id)),
+            )
+            .await;
+            log::info!(
+                "User '{}' exported the data of user {}.",
+                admin.username,
+                user_id
+            );
+            export_response(&export)
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}))
+        }
+        Err(e) => {
+            log::error!("Failed to export data of user {}: {:?}", user_id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error exporting data"}))
+        }
+    }
+}
+
+#[post(
+    "/api/admin/users/{id}/erase",
+    wrap = "RequirePermission::new(USERS_MANAGE)"
+)]
+async fn erase_user_as_admin(
+    pool: web::Data<SqlitePool>,
+    admin: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<i64>,
+) -> impl Responder {
+    let user_id = path.into_inner();
+
+    match sqlx::query_scalar::<_, String>("SELECT role FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(Some(role)) if role == ROLE_ADMIN => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": "Demote the admin to another role before erasing them"
+            }));
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}));
+        }
+        Err(e) => {
+            log::error!("Failed to look up user {}: {:?}", user_id, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error erasing user"}));
+        }
+    }
+
+    match erase_user(pool.get_ref(), user_id).await {
+        Ok(true) => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::AccountErased,
+                None,
+                None,
+                Some(&format!("user {} by user {}", user_id, admin.id)),
+            )
+            .await;
+            log::info!("User '{}' erased user {}.", admin.username, user_id);
+            HttpResponse::Ok().json(serde_json::json!({"message": "User erased"}))
+        }
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"})),
+        Err(e) => {
+            log::error!("Failed to erase user {}: {:?}", user_id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error erasing user"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(export_me)
+        .service(export_user_as_admin)
+        .service(erase_user_as_admin);
+}
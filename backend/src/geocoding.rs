@@ -0,0 +1,221 @@
+// backend/src/geocoding.rs
+// --- Geocoding ---
+// City lookup for weather requests against the local `cities` table, imported from a
+// GeoNames dump with `scripts/import_geonames.py`. Names are compared in a folded form
+// (lowercase ASCII, æ/ø/å spelled ae/oe/aa), so "Århus", "Aarhus" and "AARHUS" are
+// the same city, and small typos such as "Kobenhavn" still find a match.
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+// Ties between equally good matches go to Danish cities, then to the larger city.
+const PREFERRED_COUNTRY: &str = "DK";
+// Upper bound on the names compared one by one during a fuzzy lookup.
+const MAX_FUZZY_CANDIDATES: i64 = 5000;
+const DEFAULT_SUGGESTIONS: i64 = 10;
+const MAX_SUGGESTIONS: i64 = 25;
+
+#[derive(Serialize, FromRow, Debug, Clone)]
+pub struct City {
+    pub id: i64,
+    pub name: String,
+    pub country_code: String,
+    pub admin1_code: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub population: i64,
+}
+
+#[derive(FromRow, Debug)]
+struct Candidate {
+    search_name: String,
+    #[sqlx(flatten)]
+    city: City,
+}
+
+/// The form names are stored and compared in: lowercase ASCII letters and digits only.
+/// Danish and German letters become their two-letter spellings, other accents are
+/// dropped, and so are spaces, punctuation and letters of other scripts.
+///
+/// `scripts/import_geonames.py` has the same table; keep the two in step.
+pub fn fold(name: &str) -> String {
+    let mut folded = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        match c {
+            'a'..='z' | '0'..='9' => folded.push(c),
+            'æ' | 'ä' => folded.push_str("ae"),
+            'ø' | 'ö' | 'œ' => folded.push_str("oe"),
+            'å' => folded.push_str("aa"),
+            'ü' => folded.push_str("ue"),
+            'ß' => folded.push_str("ss"),
+            'þ' => folded.push_str("th"),
+            'á' | 'à' | 'â' | 'ã' => folded.push('a'),
+            'é' | 'è' | 'ê' | 'ë' => folded.push('e'),
+            'í' | 'ì' | 'î' | 'ï' => folded.push('i'),
+            'ó' | 'ò' | 'ô' | 'õ' => folded.push('o'),
+            'ú' | 'ù' | 'û' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            'ç' => folded.push('c'),
+            'ñ' => folded.push('n'),
+            'ð' => folded.push('d'),
+            _ => {}
+        }
+    }
+    folded
+}
+
+/// Edits allowed between a query and a city name; short names must match exactly.
+fn max_distance(len: usize) -> usize {
+    match len {
+        0..=4 => 0,
+        5..=8 => 1,
+        _ => 2,
+    }
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions and swaps
+/// of neighbouring letters each count as one. Works on bytes, which is enough for
+/// folded names.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Folded names starting with `prefix` lie in `[prefix, prefix + "~")`, since '~'
+/// sorts after every character a folded name can contain.
+fn prefix_range(prefix: &str) -> (String, String) {
+    (prefix.to_string(), format!("{}~", prefix))
+}
+
+/// Finds the city a user most likely means by `query`: an exact match on any of its
+/// names if there is one, otherwise the closest name within a few typos.
+pub async fn lookup(pool: &SqlitePool, query: &str) -> Result<Option<City>, sqlx::Error> {
+    let folded = fold(query);
+    if folded.is_empty() {
+        return Ok(None);
+    }
+
+    let exact = sqlx::query_as::<_, City>(
+        "SELECT c.id, c.name, c.country_code, c.admin1_code, c.latitude, c.longitude,
+                c.population
+         FROM city_names n JOIN cities c ON c.id = n.city_id
+         WHERE n.search_name = ?
+         ORDER BY c.country_code = ? DESC, c.population DESC
+         LIMIT 1",
+    )
+    .bind(&folded)
+    .bind(PREFERRED_COUNTRY)
+    .fetch_optional(pool)
+    .await?;
+    if exact.is_some() {
+        return Ok(exact);
+    }
+
+    let allowed = max_distance(folded.len());
+    if allowed == 0 {
+        return Ok(None);
+    }
+
+    // Typos rarely hit the first letter, so only names sharing it are compared.
+    let (from, to) = prefix_range(&folded[..1]);
+    let candidates = sqlx::query_as::<_, Candidate>(
+        "SELECT n.search_name, c.id, c.name, c.country_code, c.admin1_code, c.latitude,
+                c.longitude, c.population
+         FROM city_names n JOIN cities c ON c.id = n.city_id
+         WHERE n.search_name >= ? AND n.search_name < ?
+           AND length(n.search_name) BETWEEN ? AND ?
+         ORDER BY c.country_code = ? DESC, c.population DESC
+         LIMIT ?",
+    )
+    .bind(from)
+    .bind(to)
+    .bind((folded.len() - allowed) as i64)
+    .bind((folded.len() + allowed) as i64)
+    .bind(PREFERRED_COUNTRY)
+    .bind(MAX_FUZZY_CANDIDATES)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = edit_distance(&folded, &candidate.search_name);
+            (distance <= allowed).then_some((distance, candidate.city))
+        })
+        .min_by_key(|(distance, city)| {
+            (
+                *distance,
+                city.country_code != PREFERRED_COUNTRY,
+                -city.population,
+            )
+        })
+        .map(|(_, city)| city))
+}
+
+// --- Handlers ---
+
+#[derive(Deserialize, Debug)]
+struct CitiesQuery {
+    prefix: String,
+    limit: Option<i64>,
+}
+
+#[get("/api/weather/cities")]
+async fn get_cities(pool: web::Data<SqlitePool>, query: web::Query<CitiesQuery>) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_SUGGESTIONS);
+    if !(1..=MAX_SUGGESTIONS).contains(&limit) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("limit must be between 1 and {}", MAX_SUGGESTIONS)
+        }));
+    }
+    let folded = fold(&query.prefix);
+    if folded.is_empty() {
+        return HttpResponse::Ok().json(serde_json::json!({ "cities": [] }));
+    }
+
+    let (from, to) = prefix_range(&folded);
+    match sqlx::query_as::<_, City>(
+        "SELECT id, name, country_code, admin1_code, latitude, longitude, population
+         FROM cities
+         WHERE id IN (SELECT city_id FROM city_names WHERE search_name >= ? AND search_name < ?)
+         ORDER BY country_code = ? DESC, population DESC
+         LIMIT ?",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(PREFERRED_COUNTRY)
+    .bind(limit)
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(cities) => HttpResponse::Ok().json(serde_json::json!({ "cities": cities })),
+        Err(e) => {
+            log::error!("Failed to look up cities for '{}': {:?}", query.prefix, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error looking up cities"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_cities);
+}
@@ -0,0 +1,198 @@
+// backend/src/health.rs
+// --- Health ---
+// Liveness and readiness probes for Docker and uptime checks. `/healthz` asks whether
+// the database answers at all; `/readyz` also checks that the schema is the version
+// this build expects and that the database's file system has room left to write.
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use actix_web::rt::time::timeout;
+use actix_web::{get, web, HttpResponse, Responder};
+use sqlx::SqlitePool;
+use sysinfo::Disks;
+use whoknows_config::Config;
+
+use crate::migrations;
+
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// File path of a SQLite `database_url`, or `None` for in-memory databases.
+fn database_path(database_url: &str) -> Option<PathBuf> {
+    let rest = database_url
+        .strip_prefix("sqlite://")
+        .or_else(|| database_url.strip_prefix("sqlite:"))?;
+    let path = rest.split('?').next().unwrap_or_default();
+    if path.is_empty() || path == ":memory:" {
+        None
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+/// Space available to us on the file system holding `path`, found through the disk
+/// with the longest mount point containing it.
+fn free_disk_bytes(path: &Path) -> Option<u64> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .canonicalize()
+        .ok()?;
+    Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .filter(|disk| dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+async fn ping(pool: &SqlitePool) -> Result<(), String> {
+    match timeout(
+        DATABASE_TIMEOUT,
+        sqlx::query_scalar::<_, i64>("SELECT 1").fetch_one(pool),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+async fn check_database(pool: &SqlitePool) -> bool {
+    match ping(pool).await {
+        Ok(()) => true,
+        Err(e) => {
+            log::error!("Health check failed to reach the database: {}", e);
+            false
+        }
+    }
+}
+
+async fn check_schema(pool: &SqlitePool) -> bool {
+    let expected = migrations::latest_version();
+    match timeout(DATABASE_TIMEOUT, migrations::schema_version(pool)).await {
+        Ok(Ok(found)) => {
+            let ok = found == Some(expected);
+            if !ok {
+                log::warn!(
+                    "Database schema version {:?} does not match expected {}.",
+                    found,
+                    expected
+                );
+            }
+            ok
+        }
+        Ok(Err(e)) => {
+            log::error!("Health check failed to read the schema version: {:?}", e);
+            false
+        }
+        Err(_) => {
+            log::error!("Health check timed out reading the schema version.");
+            false
+        }
+    }
+}
+
+/// Passes for databases that aren't files, which take no disk space.
+async fn check_disk(settings: &Config) -> bool {
+    let min_free_mb = settings.backend.health.min_free_disk_mb;
+    let Some(path) = database_path(&settings.backend.database_url) else {
+        return true;
+    };
+    match web::block(move || free_disk_bytes(&path)).await {
+        Ok(Some(free_bytes)) => {
+            let free_mb = free_bytes / (1024 * 1024);
+            let ok = free_mb >= min_free_mb;
+            if !ok {
+                log::warn!(
+                    "Only {} MiB free for the database, below the {} MiB minimum.",
+                    free_mb,
+                    min_free_mb
+                );
+            }
+            ok
+        }
+        Ok(None) => {
+            log::error!("Health check found no file system holding the database.");
+            false
+        }
+        Err(e) => {
+            log::error!("Failed to read free disk space: {:?}", e);
+            false
+        }
+    }
+}
+
+fn outcome(ok: bool) -> &'static str {
+    if ok {
+        "pass"
+    } else {
+        "fail"
+    }
+}
+
+/// Runs the readiness check against a backend listening on `port` on this machine, for
+/// `backend healthcheck` in the Docker image, which has no curl.
+pub fn probe(port: u16) -> bool {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) else {
+        return false;
+    };
+    if stream.set_read_timeout(Some(PROBE_TIMEOUT)).is_err()
+        || stream
+            .write_all(b"GET /readyz HTTP/1.0\r\nHost: localhost\r\n\r\n")
+            .is_err()
+    {
+        return false;
+    }
+    let mut status_line = String::new();
+    if BufReader::new(stream).read_line(&mut status_line).is_err() {
+        return false;
+    }
+    status_line.split_whitespace().nth(1) == Some("200")
+}
+
+// --- Handlers ---
+
+#[get("/healthz")]
+async fn get_healthz(pool: web::Data<SqlitePool>) -> impl Responder {
+    match ping(pool.get_ref()).await {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({"status": "ok"})),
+        Err(e) => {
+            log::error!("Liveness check failed to reach the database: {}", e);
+            HttpResponse::ServiceUnavailable().json(serde_json::json!({"status": "unavailable"}))
+        }
+    }
+}
+
+#[get("/readyz")]
+async fn get_readyz(pool: web::Data<SqlitePool>, settings: web::Data<Config>) -> impl Responder {
+    let database = check_database(pool.get_ref()).await;
+    let schema = check_schema(pool.get_ref()).await;
+    let disk = check_disk(settings.get_ref()).await;
+
+    // Public, so only pass or fail; the reasons are in the log.
+    let body = |status: &str| {
+        serde_json::json!({
+            "status": status,
+            "checks": {
+                "database": outcome(database),
+                "schema": outcome(schema),
+                "disk": outcome(disk),
+            }
+        })
+    };
+    if database && schema && disk {
+        HttpResponse::Ok().json(body("ready"))
+    } else {
+        HttpResponse::ServiceUnavailable().json(body("not_ready"))
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_healthz).service(get_readyz);
+}
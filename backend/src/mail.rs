@@ -0,0 +1,112 @@
+// backend/src/mail.rs
+// --- Outgoing Mail ---
+// Plain-text mail through the transport `MAIL_TRANSPORT` picks: an SMTP relay, the log
+// (development only, as bodies carry working links), or none, in which case `send`
+// fails and whatever needed the mail has to tell the user so.
+use std::fmt;
+use std::sync::OnceLock;
+
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use whoknows_config::{MailSettings, MailTransportKind};
+
+enum Transport {
+    None,
+    Log,
+    Smtp {
+        mailer: Box<AsyncSmtpTransport<Tokio1Executor>>,
+        from: Mailbox,
+    },
+}
+
+static TRANSPORT: OnceLock<Transport> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum MailError {
+    NotConfigured,
+    InvalidRecipient(String),
+    Send(String),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::NotConfigured => write!(f, "no mail transport configured"),
+            MailError::InvalidRecipient(e) => write!(f, "invalid recipient: {}", e),
+            MailError::Send(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Sets up the configured transport. SMTP connections are only made when mail is sent.
+pub fn init(settings: &MailSettings) -> Result<(), String> {
+    let transport = match settings.transport {
+        MailTransportKind::None => {
+            log::warn!("No mail transport configured; email changes and notices will fail.");
+            Transport::None
+        }
+        MailTransportKind::Log => {
+            log::warn!("Mail is written to the log instead of sent (MAIL_TRANSPORT=log).");
+            Transport::Log
+        }
+        MailTransportKind::Smtp => {
+            let from = settings
+                .from
+                .parse::<Mailbox>()
+                .map_err(|e| format!("Invalid MAIL_FROM '{}': {}", settings.from, e))?;
+            let mailer = AsyncSmtpTransport::<Tokio1Executor>::from_url(&settings.smtp_url)
+                .map_err(|e| format!("Invalid SMTP_URL: {}", e))?
+                .build();
+            log::info!("Mail is sent through SMTP as {}.", from);
+            Transport::Smtp {
+                mailer: Box::new(mailer),
+                from,
+            }
+        }
+    };
+    TRANSPORT
+        .set(transport)
+        .map_err(|_| "Mail transport already initialised".to_string())
+}
+
+/// Sends a plain-text mail. Only recipient and subject are logged, except with the log
+/// transport: bodies carry working credentials, such as email verification links.
+pub async fn send(to: &str, subject: &str, body: &str) -> Result<(), MailError> {
+    match TRANSPORT.get_or_init(|| Transport::None) {
+        Transport::None => {
+            log::warn!(
+                "Mail to <{}>, subject \"{}\" not sent: no transport configured.",
+                to,
+                subject
+            );
+            Err(MailError::NotConfigured)
+        }
+        Transport::Log => {
+            log::info!(
+                "Mail to <{}>, subject \"{}\" (MAIL_TRANSPORT=log):\n{}",
+                to,
+                subject,
+                body
+            );
+            Ok(())
+        }
+        Transport::Smtp { mailer, from } => {
+            let recipient = to
+                .parse::<Mailbox>()
+                .map_err(|e| MailError::InvalidRecipient(e.to_string()))?;
+            let message = Message::builder()
+                .from(from.clone())
+                .to(recipient)
+                .subject(subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(body.to_string())
+                .map_err(|e| MailError::Send(e.to_string()))?;
+            mailer
+                .send(message)
+                .await
+                .map_err(|e| MailError::Send(e.to_string()))?;
+            log::info!("Sent mail to <{}>, subject \"{}\".", to, subject);
+            Ok(())
+        }
+    }
+}
@@ -1,19 +1,44 @@
 // backend/src/main.rs
+mod account;
+mod audit;
+mod auth;
+mod cli;
+mod csrf;
+mod db_tracing;
+mod diagnostics;
+mod gdpr;
+mod geocoding;
+mod health;
+mod mail;
+mod metrics;
+mod migrations;
+mod oidc;
+mod password;
+mod policy;
+mod pow;
+mod rbac;
+mod registration;
+mod sessions;
+mod tokens;
+mod weather;
+
 // --- Essential Actix and Web Imports ---
 use actix_cors::Cors;
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 
-// --- Prometheus Monitoring ---
-// Removed prometheus imports as they're now in the frontend
+// --- Command Line ---
+use clap::Parser;
+use cli::Command;
 
-use std::env;
+// --- Configuration ---
+use whoknows_config::{Config, Service, TracingExporter};
 
 // --- Serialization/Deserialization ---
 use serde::{Deserialize, Serialize};
 use serde_json;
 
 // --- Database (Sqlx) ---
-use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use sqlx::{FromRow, SqlitePool};
 
 // --- Date/Time ---
@@ -23,28 +48,18 @@ use chrono::NaiveDateTime;
 use log;
 
 // --- Session/Cookies/Flash ---
-use actix_session::{storage::CookieSessionStore, Session, SessionMiddleware}; // Added SessionMiddleware back
-use actix_web::cookie::{Key, SameSite}; // Kept SameSite
+use actix_session::config::{PersistentSession, TtlExtensionPolicy};
+use actix_session::{Session, SessionMiddleware};
+use actix_web::cookie::{time::Duration, Key, SameSite}; // Kept SameSite
+use sessions::SqliteSessionStore;
 use actix_web_flash_messages::{storage::CookieMessageStore, FlashMessage, FlashMessagesFramework};
 
-// --- Password Hashing & Randomness ---
-use argon2::{
-    // Added PasswordHash back, kept PasswordVerifier
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
-};
-// --- Hex Decoding for Key ---
-use hex; // Import the hex crate
-
-// Define constants for environment variable names
-const DATABASE_URL_KEY: &str = "DATABASE_URL";
-const BACKEND_INTERNAL_PORT_KEY: &str = "BACKEND_INTERNAL_PORT";
-const RUST_LOG_KEY: &str = "RUST_LOG";
-const BUILD_VERSION_KEY: &str = "BUILD_VERSION";
-const SESSION_SECRET_KEY_KEY: &str = "SESSION_SECRET_KEY";
-
-// --- Prometheus Metrics ---
-// Removed lazy_static block for HTTP_REQUESTS_TOTAL
+// --- Password Hashing ---
+use password::{hash_password, verify_password};
+
+// --- Authentication ---
+use audit::{AuthEvent, ClientInfo};
+use registration::RegistrationMode;
 
 // --- Struct Definitions ---
 
@@ -73,9 +88,7 @@ struct User {
 
 #[derive(Serialize)]
 struct ConfigResponse {
-    db_url: String,
     port: String,
-    environment: String,
     build_version: String,
 }
 
@@ -85,33 +98,17 @@ struct RegistrationForm {
     email: String,
     password: String,
     password2: String,
+    invite_code: Option<String>,
+    pow_challenge: Option<String>,
+    pow_solution: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 struct LoginForm {
     username: String,
     password: String,
-}
-
-// --- Helper Functions ---
-fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), &salt)?
-        .to_string();
-    Ok(password_hash)
-}
-
-fn verify_password(
-    stored_hash: &str,
-    password_provided: &str,
-) -> Result<bool, argon2::password_hash::Error> {
-    // Use PasswordHash here - FIXED
-    let parsed_hash = PasswordHash::new(stored_hash)?;
-    Ok(Argon2::default()
-        .verify_password(password_provided.as_bytes(), &parsed_hash)
-        .is_ok())
+    pow_challenge: Option<String>,
+    pow_solution: Option<String>,
 }
 
 // --- Configuration ---
@@ -131,22 +128,18 @@ async fn get_about() -> impl Responder {
 }
 
 #[get("/config")]
-async fn config() -> impl Responder {
-    let db_url = env::var(DATABASE_URL_KEY).unwrap_or_else(|_| "Not Set".to_string());
-    let port = env::var(BACKEND_INTERNAL_PORT_KEY).unwrap_or_else(|_| "Not Set".to_string());
-    let environment = env::var(RUST_LOG_KEY).unwrap_or_else(|_| "Not Set".to_string());
-    let build_version = env::var(BUILD_VERSION_KEY).unwrap_or_else(|_| "dev".to_string());
-
+async fn config(settings: web::Data<Config>) -> impl Responder {
+    // Public, so nothing more; administrators get the full picture from
+    // /api/admin/diagnostics.
     web::Json(ConfigResponse {
-        db_url,
-        port,
-        environment,
-        build_version,
+        port: settings.backend.port.to_string(),
+        build_version: settings.backend.build_version.clone(),
     })
 }
 
 #[post("/api/login")]
 async fn post_login(
+    req: HttpRequest,
     pool: web::Data<SqlitePool>,
     payload: web::Json<LoginForm>,
     session: Session,
@@ -159,6 +152,69 @@ async fn post_login(
     }
 
     let username = login_data.username.trim();
+    let client = ClientInfo::from_http_request(&req);
+
+    // --- Lockout Check ---
+    // Per client address, so failures from elsewhere can't lock the owner out.
+    match audit::recent_failures_from(pool.get_ref(), username, client.ip.as_deref()).await {
+        Ok(failures) if failures >= audit::LOCKOUT_THRESHOLD => {
+            log::warn!(
+                "Login for username '{}' from {:?} refused: locked out.",
+                username,
+                client.ip
+            );
+            // Linked to the account when there is one, so erasure and export find it.
+            let user_id = sqlx::query_scalar::<_, i64>("SELECT id FROM users WHERE username = ?")
+                .bind(username)
+                .fetch_optional(pool.get_ref())
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to look up user '{}': {:?}", username, e);
+                    None
+                });
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::Lockout,
+                user_id,
+                Some(username),
+                None,
+            )
+            .await;
+            return HttpResponse::TooManyRequests().json(serde_json::json!({
+                "error": "Too many failed login attempts, try again later"
+            }));
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Database error checking lockout for '{}': {:?}", username, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Login failed (database error)"}));
+        }
+    }
+    let failures = match audit::recent_failures(pool.get_ref(), username).await {
+        Ok(failures) => failures,
+        Err(e) => {
+            log::error!("Database error counting failures for '{}': {:?}", username, e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Login failed (database error)"}));
+        }
+    };
+
+    // --- Proof of Work after repeated failures, from any address ---
+    if pow::required_for_login(failures) {
+        if let Err(e) = pow::verify(
+            pool.get_ref(),
+            login_data.pow_challenge.as_deref(),
+            login_data.pow_solution.as_deref(),
+            pow::Purpose::Login,
+        )
+        .await
+        {
+            log::warn!("Login for username '{}' refused: {}.", username, e.code());
+            return e.to_response();
+        }
+    }
 
     // --- Find User by Username --- Using sqlx::query! ---
     match sqlx::query!(
@@ -178,11 +234,27 @@ async fn post_login(
             match verify_password(&user_password, &login_data.password) {
                 Ok(true) => {
                     log::info!("User '{}' logged in successfully.", user_username);
-                    if let Err(e) = session.insert("user_id", user_id) {
+                    password::rehash_if_needed(
+                        pool.get_ref(),
+                        user_id,
+                        &user_password,
+                        &login_data.password,
+                    )
+                    .await;
+                    if let Err(e) = sessions::start_login(&session, &client, user_id) {
                         log::error!("Failed to insert user_id into session: {:?}", e);
                         return HttpResponse::InternalServerError()
                             .json(serde_json::json!({"error": "Login failed (session error)"}));
                     }
+                    audit::record(
+                        pool.get_ref(),
+                        &client,
+                        AuthEvent::LoginSucceeded,
+                        Some(user_id),
+                        Some(&user_username),
+                        None,
+                    )
+                    .await;
                     FlashMessage::info("You were logged in!").send();
                     HttpResponse::Ok().json(serde_json::json!({
                         "success": true,
@@ -199,6 +271,15 @@ async fn post_login(
                         "Failed login attempt for user '{}': Invalid password.",
                         user_username
                     );
+                    audit::record(
+                        pool.get_ref(),
+                        &client,
+                        AuthEvent::LoginFailed,
+                        Some(user_id),
+                        Some(&user_username),
+                        Some("invalid password"),
+                    )
+                    .await;
                     HttpResponse::Unauthorized()
                         .json(serde_json::json!({"error": "Invalid username or password"}))
                 }
@@ -214,7 +295,18 @@ async fn post_login(
             }
         }
         Ok(None) => {
+            // Same Argon2 work as a wrong password, so timing doesn't reveal the username.
+            password::verify_dummy(&login_data.password);
             log::warn!("Failed login attempt: Username '{}' not found.", username);
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::LoginFailed,
+                None,
+                Some(username),
+                Some("unknown username"),
+            )
+            .await;
             HttpResponse::Unauthorized()
                 .json(serde_json::json!({"error": "Invalid username or password"}))
         }
@@ -231,13 +323,36 @@ async fn post_login(
     // --- End of User Find/Verify Block ---
 }
 
-#[get("/api/logout")]
-async fn get_logout(session: Session) -> impl Responder {
+#[post("/api/logout")]
+async fn post_logout(
+    req: HttpRequest,
+    pool: web::Data<SqlitePool>,
+    session: Session,
+    client: ClientInfo,
+) -> impl Responder {
     // Inject the Session object
     log::info!("Logout request received.");
 
+    // Not `Option<AuthenticatedUser>`, which would turn an invalid token into anonymous.
+    let user = match auth::resolve_user(&req).await {
+        Ok(user) => user,
+        Err(e) => return e.error_response(),
+    };
+
+    if let Some(user) = &user {
+        audit::record(
+            pool.get_ref(),
+            &client,
+            AuthEvent::Logout,
+            Some(user.id),
+            Some(&user.username),
+            None,
+        )
+        .await;
+    }
+
     // Clear the session data.
-    // purge() invalidates the session state contained in the cookie.
+    // purge() deletes the server-side session row and expires the cookie.
     session.purge();
     log::info!("Session purged.");
 
@@ -253,8 +368,28 @@ async fn get_logout(session: Session) -> impl Responder {
 async fn post_register(
     pool: web::Data<SqlitePool>,
     payload: web::Json<RegistrationForm>,
+    client: ClientInfo,
 ) -> impl Responder {
     let registration_data = payload.into_inner();
+    let mode = registration::mode();
+
+    if mode == RegistrationMode::Closed {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Registration is closed",
+            "code": "registration_closed"
+        }));
+    }
+    let invite_code = registration_data
+        .invite_code
+        .as_deref()
+        .map(str::trim)
+        .filter(|code| !code.is_empty());
+    if mode == RegistrationMode::InviteOnly && invite_code.is_none() {
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "An invite code is required to register",
+            "code": "invite_required"
+        }));
+    }
 
     if registration_data.username.trim().is_empty() {
         return HttpResponse::BadRequest()
@@ -276,42 +411,106 @@ async fn post_register(
     let username = registration_data.username.trim();
     let email = registration_data.email.trim();
 
+    if let Err(violation) = policy::check(&registration_data.password, username, email).await {
+        log::warn!(
+            "Registration for username '{}' rejected: {}",
+            username,
+            violation.code()
+        );
+        return violation.to_response();
+    }
+
+    // Checked last among the cheap validations, so a typo doesn't burn a solved challenge.
+    if pow::required_for_registration() {
+        if let Err(e) = pow::verify(
+            pool.get_ref(),
+            registration_data.pow_challenge.as_deref(),
+            registration_data.pow_solution.as_deref(),
+            pow::Purpose::Register,
+        )
+        .await
+        {
+            log::warn!("Registration for username '{}' refused: {}.", username, e.code());
+            return e.to_response();
+        }
+    }
+
+    // Hashed before the availability check, so taken and free names cost the same.
+    let hashed_password = match hash_password(&registration_data.password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::error!("Password hashing failed: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Failed to process password"}));
+        }
+    };
+
+    // The invite is used up in the same transaction that creates the user.
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Failed to start registration transaction: {:?}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error during registration"}));
+        }
+    };
+
+    // Checked before availability, so an invalid invite can't be used to probe names.
+    let invite_id = match (mode, invite_code) {
+        (RegistrationMode::InviteOnly, Some(code)) => {
+            match registration::redeem_invite(&mut tx, code).await {
+                Ok(Some(invite_id)) => Some(invite_id),
+                Ok(None) => {
+                    log::warn!("Registration for '{}' with an invalid invite code.", username);
+                    return HttpResponse::Forbidden().json(serde_json::json!({
+                        "error": "Invalid, expired or used up invite code",
+                        "code": "invite_invalid"
+                    }));
+                }
+                Err(e) => {
+                    log::error!("Database error redeeming invite: {:?}", e);
+                    return HttpResponse::InternalServerError()
+                        .json(serde_json::json!({"error": "Database error during registration"}));
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // Returning early drops `tx`, which gives back the invite use.
     match sqlx::query!(
-        "SELECT id FROM users WHERE username = ? OR email = ? LIMIT 1",
+        "SELECT username, email FROM users WHERE username = ? OR email = ?",
         username,
         email
     )
-    .fetch_optional(pool.get_ref())
+    .fetch_optional(&mut *tx)
     .await
     {
-        Ok(Some(_record_with_id)) => {
-            match sqlx::query!(
-                "SELECT username, email FROM users WHERE username = ? OR email = ?",
-                username,
-                email
-            )
-            .fetch_one(pool.get_ref())
-            .await
-            {
-                Ok(existing_user) => {
-                    let reason = if existing_user.username.eq_ignore_ascii_case(username) {
-                        "Username already taken"
-                    } else {
-                        "Email already registered"
-                    };
-                    log::warn!(
-                        "Registration conflict for username '{}': {}",
-                        username,
-                        reason
-                    );
-                    return HttpResponse::Conflict().json(serde_json::json!({"error": reason}));
-                }
-                Err(e) => {
-                    log::error!("Database error fetching existing user details: {:?}", e);
-                    return HttpResponse::InternalServerError()
-                        .json(serde_json::json!({"error": "Database error during validation"}));
+        Ok(Some(existing_user)) => {
+            if registration::uniform_responses() {
+                log::warn!(
+                    "Registration conflict for username '{}', answered uniformly.",
+                    username
+                );
+                let existing_email = Some(existing_user.email.as_str());
+                if let Err(e) = registration::notify_outcome(username, email, existing_email).await
+                {
+                    log::error!("Failed to send registration outcome mail: {}", e);
+                    return registration::uniform_mail_failed_response();
                 }
+                return registration::uniform_response();
             }
+            let reason = if existing_user.username.eq_ignore_ascii_case(username) {
+                "Username already taken"
+            } else {
+                "Email already registered"
+            };
+            log::warn!(
+                "Registration conflict for username '{}': {}",
+                username,
+                reason
+            );
+            return HttpResponse::Conflict().json(serde_json::json!({"error": reason}));
         }
         Ok(None) => {
             log::info!(
@@ -327,27 +526,40 @@ async fn post_register(
         }
     }
 
-    let hashed_password = match hash_password(&registration_data.password) {
-        Ok(hash) => hash,
-        Err(e) => {
-            log::error!("Password hashing failed: {:?}", e);
-            return HttpResponse::InternalServerError()
-                .json(serde_json::json!({"error": "Failed to process password"}));
-        }
-    };
-
     match sqlx::query!(
         "INSERT INTO users (username, email, password) VALUES (?, ?, ?)",
         username,
         email,
         hashed_password
     )
-    .execute(pool.get_ref())
+    .execute(&mut *tx)
     .await
     {
         Ok(result) => {
             if result.rows_affected() == 1 {
+                if let Err(e) = tx.commit().await {
+                    log::error!("Failed to commit registration of '{}': {:?}", username, e);
+                    return HttpResponse::InternalServerError()
+                        .json(serde_json::json!({"error": "Database error during registration"}));
+                }
                 log::info!("User '{}' registered successfully.", username);
+                audit::record(
+                    pool.get_ref(),
+                    &client,
+                    AuthEvent::Registered,
+                    Some(result.last_insert_rowid()),
+                    Some(username),
+                    invite_id.map(|id| format!("invite {}", id)).as_deref(),
+                )
+                .await;
+                rbac::promote_initial_admin(pool.get_ref()).await;
+                if registration::uniform_responses() {
+                    if let Err(e) = registration::notify_outcome(username, email, None).await {
+                        log::error!("Failed to send registration outcome mail: {}", e);
+                        return registration::uniform_mail_failed_response();
+                    }
+                    return registration::uniform_response();
+                }
                 HttpResponse::Created().json(serde_json::json!({
                     "success": true,
                     "message": "User registered successfully"
@@ -370,7 +582,23 @@ async fn post_register(
 }
 
 #[get("/api/search")]
-async fn get_search(pool: web::Data<SqlitePool>, query: web::Query<SearchQuery>) -> impl Responder {
+async fn get_search(
+    req: HttpRequest,
+    pool: web::Data<SqlitePool>,
+    query: web::Query<SearchQuery>,
+) -> impl Responder {
+    // Anonymous callers may search, but an invalid token is an error, not anonymous.
+    let user = match auth::resolve_user(&req).await {
+        Ok(user) => user,
+        Err(e) => return e.error_response(),
+    };
+    if let Some(user) = &user {
+        if !user.has_permission(rbac::SEARCH_READ) {
+            return HttpResponse::Forbidden()
+                .json(serde_json::json!({"error": "Forbidden"}));
+        }
+    }
+
     let search_term = query.q.as_deref().unwrap_or("");
     let language = query.language.as_deref().unwrap_or("en");
 
@@ -378,6 +606,21 @@ async fn get_search(pool: web::Data<SqlitePool>, query: web::Query<SearchQuery>)
         return HttpResponse::Ok().json(serde_json::json!({ "search_results": [] }));
     }
 
+    if let Some(user) = &user {
+        // Kept so users can see and export their own history; failing to record is not fatal.
+        if let Err(e) = sqlx::query(
+            "INSERT INTO search_history (user_id, query, language) VALUES (?, ?, ?)",
+        )
+        .bind(user.id)
+        .bind(search_term)
+        .bind(language)
+        .execute(pool.get_ref())
+        .await
+        {
+            log::warn!("Failed to record search history for user '{}': {:?}", user.username, e);
+        }
+    }
+
     let pattern = format!("%{}%", search_term);
 
     match sqlx::query!(
@@ -397,6 +640,7 @@ async fn get_search(pool: web::Data<SqlitePool>, query: web::Query<SearchQuery>)
                 content:     Some(rec.content),
             }}).collect();
 
+            metrics::record_search(pages.len());
             HttpResponse::Ok().json(serde_json::json!({ "search_results": pages }))
         },
         Err(e) => {
@@ -406,35 +650,97 @@ async fn get_search(pool: web::Data<SqlitePool>, query: web::Query<SearchQuery>)
     }
 }
 
-#[get("/api/weather")]
-async fn get_weather() -> impl Responder {
-    HttpResponse::Ok().body("Weather GET placeholder")
-}
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
 
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    // --- Command Line ---
+    // Parsed first, so `--help` works without a configuration.
+    let command = cli::Cli::parse().command.unwrap_or(Command::Serve);
 
-    let port_str = env::var(BACKEND_INTERNAL_PORT_KEY).unwrap_or_else(|_| "8080".to_string());
-    let port = port_str
-        .parse::<u16>()
-        .expect("BACKEND_INTERNAL_PORT must be a valid port number");
+    // --- Configuration ---
+    let settings = match Config::load(Service::Backend) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let backend = &settings.backend;
+
+    // `backend healthcheck` probes the running server, for the Docker HEALTHCHECK.
+    if let Command::Healthcheck = command {
+        std::process::exit(if health::probe(backend.port) { 0 } else { 1 });
+    }
+    diagnostics::init();
+    metrics::init(&settings.metrics, &backend.build_version);
+
+    whoknows_telemetry::init_logging(&settings.log, settings.log_format, "whoknows-backend");
+
+    // --- Tracing ---
+    let telemetry = match whoknows_telemetry::init(&settings.tracing, "whoknows-backend") {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if settings.tracing.exporter != TracingExporter::None {
+        if let Err(e) = db_tracing::init() {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
-    println!("Server starting at http://{}:{}", HOST_NAME, port);
-    log::info!("Server starting at http://{}:{}", HOST_NAME, port);
+    // --- Base URL, Password Hashing, Policy, Registration, OIDC, Proof of Work, Weather, Mail ---
+    if let Err(e) = account::init(&backend.public_base_url)
+        .and_then(|_| password::init(&backend.argon2))
+        .and_then(|_| policy::init(&backend.password))
+        .and_then(|_| registration::init(&backend.registration))
+        .and_then(|_| oidc::init(&backend.oidc))
+        .and_then(|_| pow::init(&backend.pow))
+        .and_then(|_| weather::init(&backend.weather))
+        .and_then(|_| mail::init(&backend.mail))
+        .and_then(|_| rbac::init(backend.initial_admin.as_deref()))
+        .and_then(|_| audit::init(backend.trusted_proxy_networks()))
+    {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
 
-    let database_url =
-        env::var(DATABASE_URL_KEY).expect("DATABASE_URL must be set in environment or .env file");
+    // `backend check-config` gets this far only with a valid configuration, and prints
+    // what it resolved to, secrets redacted.
+    if let Command::CheckConfig = command {
+        match serde_json::to_string_pretty(&settings) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                log::error!("Failed to print the configuration: {}", e);
+                std::process::exit(1);
+            }
+        }
+        telemetry.shutdown();
+        return Ok(());
+    }
 
+    // Only a run that may migrate creates a missing database file.
+    let may_migrate = matches!(command, Command::Migrate { .. }) || backend.migrate_on_startup;
+    let connect_options = match backend.database_url.parse::<SqliteConnectOptions>() {
+        Ok(options) => options.create_if_missing(may_migrate),
+        Err(e) => {
+            log::error!("Invalid DATABASE_URL: {}", e);
+            std::process::exit(1);
+        }
+    };
     let pool = match SqlitePoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect_with(connect_options)
         .await
     {
         Ok(p) => {
-            log::info!("Successfully connected to the database at {}", database_url);
+            log::info!(
+                "Successfully connected to the database at {}",
+                backend.database_url
+            );
             p
         }
         Err(e) => {
@@ -443,93 +749,167 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
-    // --- Load Session Key ---
-    let session_secret_key_hex =
-        env::var(SESSION_SECRET_KEY_KEY).expect("SESSION_SECRET_KEY must be set...");
-
-    // println!(
-    //     "Read SESSION_SECRET_KEY (length {}): {}",
-    //     session_secret_key_hex.len(),
-    //     session_secret_key_hex.chars().take(10).collect::<String>()
-    // );
-
-    let decoded_key_bytes = match hex::decode(&session_secret_key_hex) {
-        Ok(bytes) => {
-            println!(
-                "Successfully decoded hex key to bytes (length: {})",
-                bytes.len()
-            );
-            bytes
+    // --- Schema and Admin Commands ---
+    // `migrate` and `backup` work on any schema; the other commands need a current one.
+    if !command.skips_schema_check() {
+        if let Err(e) = migrations::prepare(&pool, backend.migrate_on_startup).await {
+            log::error!("{}", e);
+            std::process::exit(1);
         }
-        Err(e) => {
-            println!("!!! FAILED to decode hex key: {:?}", e);
-            panic!("SESSION_SECRET_KEY decoding failed: {:?}", e);
+    }
+    if !matches!(command, Command::Serve) {
+        let result = cli::run(command, &pool).await;
+        pool.close().await;
+        telemetry.shutdown();
+        if let Err(e) = result {
+            log::error!("{}", e);
+            std::process::exit(1);
         }
-    };
+        return Ok(());
+    }
+    rbac::promote_initial_admin(&pool).await;
 
-    let key_array: [u8; 32] = match decoded_key_bytes.try_into() {
-        Ok(array) => {
-            println!("Successfully converted Vec<u8> to [u8; 32]");
-            array
-        }
-        Err(vec) => {
-            println!(
-                "!!! FAILED to convert Vec<u8> to [u8; 32]. Original Vec length: {}",
-                vec.len()
-            );
-            panic!(
-                "Decoded key was not exactly 32 bytes long, length was: {}",
-                vec.len()
-            );
-        }
-    };
+    log::info!("Server starting at http://{}:{}", HOST_NAME, backend.port);
 
-    // --- Manually Construct Key (Workaround for potential Key::from bug) ---
-    // Key internally holds encryption and signing keys.
-    // For a 32-byte master key, cookie 0.16.2 splits it into two 16-byte keys.
-    // Ensure the total length provided matches the key structure.
-    println!("Attempting manual Key construction...");
-    let session_secret_key = Key::derive_from(&key_array); // derive_from should handle splitting
-    println!("Manual Key construction (derive_from) succeeded.");
+    // --- Load Session Key ---
+    // Validated as 32 hex-encoded bytes when the configuration was loaded.
+    let key_bytes = backend
+        .session
+        .secret_key_bytes()
+        .expect("session secret key checked by Config::load");
+    let session_secret_key = Key::derive_from(&key_bytes);
+
+    // --- Session Expiry ---
+    let session_idle_timeout = backend.session.idle_timeout_secs;
+    let session_absolute_timeout = backend.session.absolute_timeout_secs;
+    log::info!(
+        "Sessions expire after {}s idle, {}s absolute.",
+        session_idle_timeout,
+        session_absolute_timeout
+    );
+    let session_store =
+        SqliteSessionStore::new(pool.clone(), Duration::seconds(session_absolute_timeout));
 
     // --- Setup Flash Messages ---
     let message_store = CookieMessageStore::builder(session_secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
 
+    let port = backend.port;
+    let public_origin = backend.public_origin();
+    let settings = web::Data::new(settings);
+
     // --- Start Actix HTTP Server --- FIXED SESSION MIDDLEWARE ---
-    HttpServer::new(move || {
+    let result = HttpServer::new(move || {
         // --- Create Session Middleware INSIDE the closure ---
         let session_middleware = SessionMiddleware::builder(
-            CookieSessionStore::default(),
+            session_store.clone(),
             session_secret_key.clone(), // Clone the key
         )
+        .session_lifecycle(
+            PersistentSession::default()
+                .session_ttl(Duration::seconds(session_idle_timeout))
+                .session_ttl_extension_policy(TtlExtensionPolicy::OnEveryRequest),
+        )
         .cookie_secure(true) // Set true for HTTPS
         .cookie_same_site(SameSite::Lax) // Use SameSite here
         .cookie_http_only(true)
         .build();
 
+        // Only the site itself; API clients with a token send no Origin
         let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header();
+            .allowed_origin(&public_origin)
+            .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+            .allowed_headers(vec!["Content-Type", "Accept", "Authorization", csrf::CSRF_HEADER])
+            .supports_credentials()
+            .max_age(3600);
 
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(settings.clone())
+            .wrap(csrf::CsrfProtection) // Innermost, so it can read the session
             .wrap(cors)
             .wrap(message_framework.clone())
             .wrap(session_middleware) // Now this works
+            .wrap(db_tracing::QueryScope) // So session and handler queries join the trace
+            .wrap(metrics::RequestMetrics) // Times everything below
+            .wrap(whoknows_telemetry::RequestId) // Inside the trace, so log lines name it
+            .wrap(whoknows_telemetry::TraceRequests) // Outermost, so every span nests under it
             .service(hello)
             .service(config)
             .service(get_about)
             .service(post_login) // Ensure registered
             .service(post_register)
-            .service(get_logout)
+            .service(post_logout)
             .service(get_search)
-            .service(get_weather)
-        // Removed metrics service registration
+            .configure(csrf::configure)
+            .configure(diagnostics::configure)
+            .configure(health::configure)
+            .configure(metrics::configure)
+            .configure(tokens::configure)
+            .configure(rbac::configure)
+            .configure(sessions::configure)
+            .configure(account::configure)
+            .configure(gdpr::configure)
+            .configure(audit::configure)
+            .configure(oidc::configure)
+            .configure(registration::configure)
+            .configure(pow::configure)
+            .configure(weather::configure)
+            .configure(geocoding::configure)
         // Removed duplicate/unused service registrations
     })
     .bind((HOST_NAME, port))?
     .run()
-    .await
+    .await;
+
+    telemetry.shutdown();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn search_rejects_an_invalid_token_instead_of_serving_it_anonymously() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(migrations::memory_pool().await))
+                .service(get_search),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/search?q=rust")
+            .insert_header((header::AUTHORIZATION, "Bearer wk_not-a-token"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let req = test::TestRequest::get().uri("/api/search?q=rust").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn logout_rejects_an_invalid_token() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(migrations::memory_pool().await))
+                .service(post_logout),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/logout")
+            .insert_header((header::AUTHORIZATION, "Bearer wk_not-a-token"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
 }
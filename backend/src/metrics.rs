@@ -0,0 +1,197 @@
+// backend/src/metrics.rs
+// --- Prometheus Metrics ---
+// `GET /metrics` for Prometheus: request latency and status codes per route, database
+// pool usage and search result counts. The backend port is only exposed on the compose
+// network, so the endpoint is left open for the scraper. Label values pass through the
+// shared `LabelPolicy`, so clients can't create series at will.
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{get, web, Error, HttpResponse, Responder};
+use futures::future::{self, LocalBoxFuture, Ready};
+use lazy_static::lazy_static;
+use prometheus::{
+    register_gauge, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, Encoder, Gauge,
+    Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use sqlx::SqlitePool;
+use whoknows_config::{LabelPolicy, MetricsSettings};
+
+/// Route label for requests that matched no route, so probes for random paths don't
+/// create a series each.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// How long a scrape waits for a pool connection before reporting the pool as exhausted.
+const POOL_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(1);
+
+static LABEL_POLICY: OnceLock<LabelPolicy> = OnceLock::new();
+
+lazy_static! {
+    static ref BUILD_INFO: IntGaugeVec = register_int_gauge_vec!(
+        "backend_build_info",
+        "Always 1; the version label carries BUILD_VERSION",
+        &["version"]
+    )
+    .unwrap();
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time from receiving a request to producing its response, per route",
+        &["method", "route"]
+    )
+    .unwrap();
+    static ref HTTP_RESPONSES_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "http_responses_total",
+        "Responses sent, per route and status code",
+        &["method", "route", "status"]
+    )
+    .unwrap();
+    static ref DB_POOL_CONNECTIONS: IntGauge = register_int_gauge!(
+        "db_pool_connections",
+        "Open database connections, idle or in use"
+    )
+    .unwrap();
+    static ref DB_POOL_IDLE_CONNECTIONS: IntGauge = register_int_gauge!(
+        "db_pool_idle_connections",
+        "Open database connections not in use"
+    )
+    .unwrap();
+    static ref DB_POOL_MAX_CONNECTIONS: IntGauge =
+        register_int_gauge!("db_pool_max_connections", "Connections the pool may open").unwrap();
+    static ref DB_POOL_ACQUIRE_WAIT: Gauge = register_gauge!(
+        "db_pool_acquire_wait_seconds",
+        "Time the last scrape waited for a pool connection; 1 when it gave up"
+    )
+    .unwrap();
+    static ref SEARCH_RESULTS: Histogram = register_histogram!(
+        "search_results",
+        "Pages returned per search",
+        vec![0.0, 1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0]
+    )
+    .unwrap();
+    static ref SEARCH_ZERO_RESULTS_TOTAL: IntCounter =
+        register_int_counter!("search_zero_results_total", "Searches that found no pages").unwrap();
+}
+
+/// Sets the label policy and publishes the build version; call once at startup.
+pub fn init(settings: &MetricsSettings, build_version: &str) {
+    let _ = LABEL_POLICY.set(LabelPolicy::new(settings));
+    BUILD_INFO.with_label_values(&[build_version]).set(1);
+}
+
+fn label_policy() -> &'static LabelPolicy {
+    LABEL_POLICY.get_or_init(|| LabelPolicy::new(&MetricsSettings::default()))
+}
+
+/// Counts one search that returned `results` pages.
+pub fn record_search(results: usize) {
+    SEARCH_RESULTS.observe(results as f64);
+    if results == 0 {
+        SEARCH_ZERO_RESULTS_TOTAL.inc();
+    }
+}
+
+async fn update_pool_metrics(pool: &SqlitePool) {
+    DB_POOL_CONNECTIONS.set(i64::from(pool.size()));
+    DB_POOL_IDLE_CONNECTIONS.set(pool.num_idle() as i64);
+    DB_POOL_MAX_CONNECTIONS.set(i64::from(pool.options().get_max_connections()));
+
+    let started = Instant::now();
+    let waited = match actix_web::rt::time::timeout(POOL_ACQUIRE_TIMEOUT, pool.acquire()).await {
+        Ok(Ok(_connection)) => started.elapsed(),
+        Ok(Err(e)) => {
+            log::warn!("Failed to acquire a connection for pool metrics: {:?}", e);
+            POOL_ACQUIRE_TIMEOUT
+        }
+        Err(_) => POOL_ACQUIRE_TIMEOUT,
+    };
+    DB_POOL_ACQUIRE_WAIT.set(waited.as_secs_f64());
+}
+
+// --- Request Metrics Middleware ---
+
+/// Times every request and counts its status under the route pattern it matched, e.g.
+/// `/api/admin/users/{id}/role` rather than the concrete path.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(RequestMetricsMiddleware { service })
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let result = fut.await;
+            let (route, status) = match &result {
+                Ok(res) => (res.request().match_pattern(), res.status()),
+                Err(e) => (None, e.as_response_error().status_code()),
+            };
+            let route = route.unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+            let policy = label_policy();
+            let method = policy.method(&method);
+            let route = policy.bounded("http_request_duration_seconds", "route", &route);
+            let status = policy.bounded("http_responses_total", "status", status.as_str());
+            HTTP_REQUEST_DURATION
+                .with_label_values(&[method, route])
+                .observe(started.elapsed().as_secs_f64());
+            HTTP_RESPONSES_TOTAL
+                .with_label_values(&[method, route, status])
+                .inc();
+            result
+        })
+    }
+}
+
+// --- Handlers ---
+
+#[get("/metrics")]
+async fn get_metrics(pool: web::Data<SqlitePool>) -> impl Responder {
+    update_pool_metrics(pool.get_ref()).await;
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        log::error!("Failed to encode Prometheus metrics: {:?}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(buffer)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_metrics);
+}
@@ -0,0 +1,251 @@
+// backend/src/migrations.rs
+// --- Schema Migrations ---
+// The schema is the numbered migrations in `migrations/`, embedded in the binary; each
+// has an `.up.sql` and a `.down.sql`. At startup the database's version is compared
+// with the newest migration: pending ones are applied when `MIGRATE_ON_STARTUP` is set,
+// and the server refuses to start on a schema it doesn't know. Databases created from
+// the old `db-migration/whoknows.tables.sql` have no migrations table; when their tables
+// match migration 1 they are adopted as version 1 the first time they migrate.
+use std::fmt;
+
+use sqlx::migrate::{Migrate, Migration, Migrator};
+use sqlx::SqlitePool;
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Migration holding the schema of `db-migration/whoknows.tables.sql`.
+const BASELINE_VERSION: i64 = 1;
+/// Tables and columns of that schema, which an unversioned database must have exactly.
+const BASELINE_TABLES: &[(&str, &[&str])] = &[
+    (
+        "pages",
+        &["title", "url", "language", "last_updated", "content"],
+    ),
+    ("users", &["id", "username", "email", "password"]),
+];
+
+fn up_migrations() -> impl Iterator<Item = &'static Migration> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+}
+
+/// A fully migrated in-memory database for tests. It has one connection, as every
+/// connection to `sqlite::memory:` opens a database of its own.
+#[cfg(test)]
+pub async fn memory_pool() -> SqlitePool {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("in-memory database opens");
+    MIGRATOR.run(&pool).await.expect("migrations apply");
+    pool
+}
+
+/// The newest migration in this build, the schema version it expects.
+pub fn latest_version() -> i64 {
+    up_migrations()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
+
+async fn has_migrations_table(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(count > 0)
+}
+
+/// Names of the database's own tables, without SQLite's internal ones.
+async fn table_names(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Whether an unversioned database has exactly the tables and columns of migration 1.
+async fn is_baseline(pool: &SqlitePool, tables: &[String]) -> Result<bool, sqlx::Error> {
+    if !tables
+        .iter()
+        .map(String::as_str)
+        .eq(BASELINE_TABLES.iter().map(|(name, _)| *name))
+    {
+        return Ok(false);
+    }
+    for (table, expected) in BASELINE_TABLES {
+        let columns =
+            sqlx::query_scalar::<_, String>("SELECT name FROM pragma_table_info(?) ORDER BY cid")
+                .bind(table)
+                .fetch_all(pool)
+                .await?;
+        if !columns
+            .iter()
+            .map(String::as_str)
+            .eq(expected.iter().copied())
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Version of the database's schema: the newest applied migration, the baseline version
+/// for unversioned databases that match it, or `None` for an empty or unknown database.
+pub async fn schema_version(pool: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    if has_migrations_table(pool).await? {
+        return sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1",
+        )
+        .fetch_one(pool)
+        .await;
+    }
+    let tables = table_names(pool).await?;
+    Ok(is_baseline(pool, &tables)
+        .await?
+        .then_some(BASELINE_VERSION))
+}
+
+fn read_failed(e: impl fmt::Display) -> String {
+    format!("Failed to read the schema version: {}", e)
+}
+
+fn adopt_failed(e: impl fmt::Display) -> String {
+    format!("Failed to adopt the existing schema: {}", e)
+}
+
+/// Reasons this build can't work with the database's schema, whether or not it would
+/// migrate: tables without any version, migrations it doesn't know or that changed since
+/// they were applied, and migrations that failed part-way.
+async fn unknown_schema(pool: &SqlitePool) -> Result<Option<String>, String> {
+    if !has_migrations_table(pool).await.map_err(read_failed)? {
+        let tables = table_names(pool).await.map_err(read_failed)?;
+        if tables.is_empty() || is_baseline(pool, &tables).await.map_err(read_failed)? {
+            return Ok(None);
+        }
+        return Ok(Some(format!(
+            "Database has tables ({}) but no migrations table, and they don't match the \
+             original schema of users and pages that can be adopted as version {}.",
+            tables.join(", "),
+            BASELINE_VERSION
+        )));
+    }
+
+    let mut conn = pool.acquire().await.map_err(read_failed)?;
+    if let Some(version) = conn.dirty_version().await.map_err(read_failed)? {
+        return Ok(Some(format!(
+            "Migration {} failed part-way; repair the database by hand before starting.",
+            version
+        )));
+    }
+    for applied in conn.list_applied_migrations().await.map_err(read_failed)? {
+        match up_migrations().find(|migration| migration.version == applied.version) {
+            None => {
+                return Ok(Some(format!(
+                    "Database has migration {}, which this build doesn't know (latest is {}); \
+                     it was probably applied by a newer release.",
+                    applied.version,
+                    latest_version()
+                )))
+            }
+            Some(migration) if migration.checksum != applied.checksum => {
+                return Ok(Some(format!(
+                    "Migration {} was changed after it was applied to this database.",
+                    applied.version
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Records an unversioned database matching migration 1 as having it applied, without
+/// running it, so sqlx takes over from there.
+async fn adopt_baseline(pool: &SqlitePool) -> Result<(), String> {
+    if has_migrations_table(pool).await.map_err(adopt_failed)?
+        || schema_version(pool).await.map_err(adopt_failed)? != Some(BASELINE_VERSION)
+    {
+        return Ok(());
+    }
+    let migration = up_migrations()
+        .find(|migration| migration.version == BASELINE_VERSION)
+        .ok_or("The initial migration is missing from this build")?;
+
+    let mut tx = pool.begin().await.map_err(adopt_failed)?;
+    tx.ensure_migrations_table().await.map_err(adopt_failed)?;
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES (?1, ?2, TRUE, ?3, 0)",
+    )
+    .bind(migration.version)
+    .bind(migration.description.as_ref())
+    .bind(migration.checksum.as_ref())
+    .execute(&mut *tx)
+    .await
+    .map_err(adopt_failed)?;
+    tx.commit().await.map_err(adopt_failed)?;
+    log::info!(
+        "Adopted the existing schema as migration {}.",
+        BASELINE_VERSION
+    );
+    Ok(())
+}
+
+/// Makes sure the schema is the one this build expects before serving: applies pending
+/// migrations when `migrate` is set, and fails otherwise or when the schema is unknown.
+pub async fn prepare(pool: &SqlitePool, migrate: bool) -> Result<(), String> {
+    if let Some(problem) = unknown_schema(pool).await? {
+        return Err(problem);
+    }
+    let found = schema_version(pool).await.map_err(read_failed)?;
+    let latest = latest_version();
+    if found == Some(latest) {
+        log::info!("Database schema is at version {}.", latest);
+        return Ok(());
+    }
+    if !migrate {
+        return Err(format!(
+            "Database schema is at version {} but this build needs {}; run `backend migrate` \
+             or set MIGRATE_ON_STARTUP=true.",
+            found.map_or("none".to_string(), |version| version.to_string()),
+            latest
+        ));
+    }
+
+    adopt_baseline(pool).await?;
+    MIGRATOR
+        .run(pool)
+        .await
+        .map_err(|e| format!("Failed to apply migrations: {}", e))?;
+    log::info!(
+        "Migrated the database schema from version {} to {}.",
+        found.unwrap_or(0),
+        latest
+    );
+    Ok(())
+}
+
+/// Reverts every applied migration newer than `target`, running their `.down.sql`.
+pub async fn undo(pool: &SqlitePool, target: i64) -> Result<(), String> {
+    if let Some(problem) = unknown_schema(pool).await? {
+        return Err(problem);
+    }
+    adopt_baseline(pool).await?;
+    MIGRATOR
+        .undo(pool, target)
+        .await
+        .map_err(|e| format!("Failed to revert migrations: {}", e))?;
+    let now = schema_version(pool).await.map_err(read_failed)?;
+    log::info!(
+        "Database schema is now at version {}.",
+        now.map_or("none".to_string(), |version| version.to_string())
+    );
+    Ok(())
+}
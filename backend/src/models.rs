@@ -14,7 +14,7 @@ pub struct User {
 
 #[derive(Serialize, Deserialize, FromRow, Debug, Clone)] // Added Debug and Clone
 pub struct Page {
-    // These must match the column names in schema.sql
+    // These must match the column names in migrations/
     pub title: String,
     pub url: String,
     pub language: String,
@@ -0,0 +1,691 @@
+// backend/src/oidc.rs
+// --- OpenID Connect Login ---
+// "Sign in with ..." through an external provider, using the authorization code flow
+// with PKCE. Provider accounts are linked to local users in `user_identities`; a
+// successful callback ends in the same session `post_login` creates.
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use actix_session::Session;
+use actix_web::http::header;
+use actix_web::{get, web, HttpResponse, Responder};
+use awc::Client;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use whoknows_config::OidcSettings;
+
+use crate::account::public_base_url;
+use crate::audit::{self, AuthEvent, ClientInfo};
+use crate::password::hash_password;
+use crate::rbac;
+use crate::registration::{self, RegistrationMode};
+use crate::sessions::{self, random_string};
+
+const CALLBACK_PATH: &str = "/api/auth/oidc/callback";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+// Allowed difference between our clock and the provider's.
+const CLOCK_SKEW_SECS: i64 = 60;
+// Registrations that lose the race for a username to a concurrent one pick another.
+const REGISTRATION_ATTEMPTS: usize = 3;
+
+// Session state entries holding an in-flight login.
+const STATE_KEY: &str = "oidc_state";
+const NONCE_KEY: &str = "oidc_nonce";
+const VERIFIER_KEY: &str = "oidc_verifier";
+
+struct OidcConfig {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    authorization_url: Option<String>,
+    provider_name: String,
+    scopes: String,
+    auto_register: bool,
+    link_verified_email: bool,
+    allow_insecure_http: bool,
+    endpoints: Mutex<Option<Endpoints>>,
+}
+
+/// The parts of the provider's discovery document we use.
+#[derive(Deserialize, Clone, Debug)]
+struct Endpoints {
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+static CONFIG: OnceLock<Option<OidcConfig>> = OnceLock::new();
+
+#[derive(Deserialize, Debug)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TokenRequest<'a> {
+    grant_type: &'static str,
+    code: &'a str,
+    redirect_uri: &'a str,
+    client_id: &'a str,
+    code_verifier: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == client_id,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+
+    fn is_single(&self) -> bool {
+        match self {
+            Audience::One(_) => true,
+            Audience::Many(auds) => auds.len() == 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    azp: Option<String>,
+    exp: i64,
+    iat: i64,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    preferred_username: Option<String>,
+}
+
+/// Sets up the provider from the configuration. OIDC stays off unless an issuer URL
+/// is configured.
+pub fn init(settings: &OidcSettings) -> Result<(), String> {
+    let config = match (&settings.issuer_url, &settings.client_id) {
+        (None, _) => {
+            log::info!("No OIDC issuer configured, OIDC login disabled.");
+            None
+        }
+        (Some(_), None) => return Err("An OIDC client id is required with an issuer".into()),
+        (Some(issuer), Some(client_id)) => {
+            let config = OidcConfig {
+                issuer: issuer.trim_end_matches('/').to_string(),
+                client_id: client_id.clone(),
+                client_secret: settings.client_secret.clone(),
+                redirect_url: settings
+                    .redirect_url
+                    .clone()
+                    .unwrap_or_else(|| format!("{}{}", public_base_url(), CALLBACK_PATH)),
+                authorization_url: settings.authorization_url.clone(),
+                provider_name: settings.provider_name.clone(),
+                scopes: settings.scopes.clone(),
+                auto_register: settings.auto_register,
+                link_verified_email: settings.link_verified_email,
+                allow_insecure_http: settings.allow_insecure_http,
+                endpoints: Mutex::new(None),
+            };
+            log::info!(
+                "OIDC login enabled via '{}' ({}), redirect URL {}.",
+                config.provider_name,
+                config.issuer,
+                config.redirect_url
+            );
+            Some(config)
+        }
+    };
+    CONFIG
+        .set(config)
+        .map_err(|_| "OIDC configuration already initialised".to_string())
+}
+
+fn config() -> Option<&'static OidcConfig> {
+    CONFIG.get().and_then(Option::as_ref)
+}
+
+fn http_client() -> Client {
+    Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .disable_redirects()
+        .finish()
+}
+
+impl OidcConfig {
+    /// Discovery document endpoints, fetched on first use and then cached. A provider
+    /// that is down at startup therefore doesn't disable SSO until the next restart.
+    async fn endpoints(&self) -> Result<Endpoints, String> {
+        if let Some(endpoints) = self.endpoints.lock().expect("lock poisoned").clone() {
+            return Ok(endpoints);
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.issuer);
+        let mut response = http_client()
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Discovery request to {} failed: {}", url, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Discovery request to {} returned {}",
+                url,
+                response.status()
+            ));
+        }
+        let endpoints: Endpoints = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid discovery document at {}: {}", url, e))?;
+        // ID tokens are trusted for coming from here, so it has to be TLS.
+        if !endpoints.token_endpoint.starts_with("https://") && !self.allow_insecure_http {
+            return Err(format!(
+                "Token endpoint {} in the discovery document is not https",
+                endpoints.token_endpoint
+            ));
+        }
+
+        *self.endpoints.lock().expect("lock poisoned") = Some(endpoints.clone());
+        Ok(endpoints)
+    }
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Decodes the claims of an ID token and checks them against our client and login.
+///
+/// The signature is not checked: the token comes straight from the token endpoint
+/// over a TLS connection we opened, which OpenID Connect Core 3.1.3.7 accepts in
+/// place of signature validation for the code flow. The configuration and `endpoints`
+/// refuse plain http issuers and token endpoints unless `OIDC_ALLOW_INSECURE_HTTP` is set.
+fn validate_id_token(
+    config: &OidcConfig,
+    id_token: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, String> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| "ID token is not a JWT".to_string())?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| format!("ID token payload is not base64url: {}", e))?;
+    let claims: IdTokenClaims = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("ID token claims are invalid: {}", e))?;
+
+    let now = chrono::Utc::now().timestamp();
+    if claims.iss.trim_end_matches('/') != config.issuer {
+        return Err(format!("Unexpected issuer '{}'", claims.iss));
+    }
+    if !claims.aud.contains(&config.client_id) {
+        return Err("ID token was issued for another client".to_string());
+    }
+    if !claims.aud.is_single() && claims.azp.as_deref() != Some(config.client_id.as_str()) {
+        return Err("ID token has several audiences but no matching azp".to_string());
+    }
+    if claims.exp + CLOCK_SKEW_SECS < now {
+        return Err("ID token has expired".to_string());
+    }
+    if claims.iat - CLOCK_SKEW_SECS > now {
+        return Err("ID token was issued in the future".to_string());
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err("ID token nonce does not match".to_string());
+    }
+    Ok(claims)
+}
+
+/// Redirect back to the login page with a machine-readable error code.
+fn login_error(code: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((
+            header::LOCATION,
+            format!("{}/login.html?error={}", public_base_url(), code),
+        ))
+        .finish()
+}
+
+/// Picks a free username based on the provider's preferred username or email.
+async fn free_username(pool: &SqlitePool, claims: &IdTokenClaims) -> Result<String, sqlx::Error> {
+    let wanted = claims
+        .preferred_username
+        .as_deref()
+        .or_else(|| claims.email.as_deref().and_then(|e| e.split('@').next()))
+        .unwrap_or("user");
+    let base: String = wanted
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .take(32)
+        .collect();
+    let base = if base.is_empty() {
+        "user".to_string()
+    } else {
+        base
+    };
+
+    let mut candidate = base.clone();
+    let mut suffix = 1;
+    while sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE username = ?")
+        .bind(&candidate)
+        .fetch_one(pool)
+        .await?
+        > 0
+    {
+        suffix += 1;
+        candidate = format!("{}{}", base, suffix);
+    }
+    Ok(candidate)
+}
+
+enum Resolution {
+    User(i64),
+    Refused(&'static str),
+}
+
+/// The user a provider identity is linked to, recording the login.
+async fn linked_user(
+    pool: &SqlitePool,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "UPDATE user_identities SET email = ?, last_login_at = CURRENT_TIMESTAMP
+         WHERE issuer = ? AND subject = ?
+         RETURNING user_id",
+    )
+    .bind(&claims.email)
+    .bind(&config.issuer)
+    .bind(&claims.sub)
+    .fetch_optional(pool)
+    .await
+}
+
+async fn user_with_email(pool: &SqlitePool, email: &str) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>("SELECT id FROM users WHERE email = ?")
+        .bind(email)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Finds or creates the local user for a provider identity.
+async fn resolve_identity(
+    pool: &SqlitePool,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+    logged_in_user: Option<i64>,
+    client: &ClientInfo,
+) -> Result<Resolution, sqlx::Error> {
+    if let Some(user_id) = linked_user(pool, config, claims).await? {
+        return Ok(Resolution::User(user_id));
+    }
+
+    // Someone logged in with a password is linking their account.
+    if let Some(user_id) = logged_in_user {
+        return link(pool, config, claims, user_id).await;
+    }
+
+    let Some(email) = claims.email.as_deref().filter(|e| e.contains('@')) else {
+        return Ok(Resolution::Refused("oidc_missing_email"));
+    };
+
+    let email_verified = claims.email_verified == Some(true);
+    if let Some(existing) = user_with_email(pool, email).await? {
+        if !(config.link_verified_email && email_verified) {
+            // Log in with the password once and start the SSO login from there.
+            return Ok(Resolution::Refused("oidc_account_exists"));
+        }
+        return link(pool, config, claims, existing).await;
+    }
+
+    if !config.auto_register || registration::mode() != RegistrationMode::Open {
+        return Ok(Resolution::Refused("oidc_registration_disabled"));
+    }
+    // The new account's address is only as good as the provider's word for it.
+    if !email_verified {
+        return Ok(Resolution::Refused("oidc_email_unverified"));
+    }
+
+    // Nobody knows this password, so the account can only be used through the provider.
+    let placeholder = hash_password(&random_string(48))
+        .map_err(|e| sqlx::Error::Protocol(format!("Password hashing failed: {:?}", e)))?;
+
+    for _ in 0..REGISTRATION_ATTEMPTS {
+        let username = free_username(pool, claims).await?;
+        match register(pool, config, claims, email, &username, &placeholder).await {
+            Ok(user_id) => {
+                log::info!("User '{}' registered through OIDC.", username);
+                audit::record(
+                    pool,
+                    client,
+                    AuthEvent::Registered,
+                    Some(user_id),
+                    Some(&username),
+                    Some("oidc"),
+                )
+                .await;
+                rbac::promote_initial_admin(pool).await;
+                return Ok(Resolution::User(user_id));
+            }
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                log::warn!(
+                    "OIDC registration as '{}' collided with a concurrent one: {}",
+                    username,
+                    e.message()
+                );
+            }
+            Err(e) => return Err(e),
+        }
+
+        // The other request may have registered this identity or this email; otherwise
+        // it took the username and the next attempt picks another.
+        if let Some(user_id) = linked_user(pool, config, claims).await? {
+            return Ok(Resolution::User(user_id));
+        }
+        if user_with_email(pool, email).await?.is_some() {
+            return Ok(Resolution::Refused("oidc_account_exists"));
+        }
+    }
+    Ok(Resolution::Refused("oidc_registration_conflict"))
+}
+
+/// Creates the user and links the identity to it, both or neither.
+async fn register(
+    pool: &SqlitePool,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+    email: &str,
+    username: &str,
+    placeholder: &str,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let user_id = sqlx::query("INSERT INTO users (username, email, password) VALUES (?, ?, ?)")
+        .bind(username)
+        .bind(email)
+        .bind(placeholder)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+    sqlx::query(
+        "INSERT INTO user_identities (user_id, issuer, subject, email) VALUES (?, ?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(&config.issuer)
+    .bind(&claims.sub)
+    .bind(email)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(user_id)
+}
+
+async fn link(
+    pool: &SqlitePool,
+    config: &OidcConfig,
+    claims: &IdTokenClaims,
+    user_id: i64,
+) -> Result<Resolution, sqlx::Error> {
+    let linked = sqlx::query(
+        "INSERT INTO user_identities (user_id, issuer, subject, email) VALUES (?, ?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(&config.issuer)
+    .bind(&claims.sub)
+    .bind(&claims.email)
+    .execute(pool)
+    .await;
+    match linked {
+        Ok(_) => {
+            log::info!("Linked OIDC identity to user {}.", user_id);
+            Ok(Resolution::User(user_id))
+        }
+        // A concurrent login linked the identity first; it logs in whoever that was.
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Ok(match linked_user(pool, config, claims).await? {
+                Some(linked_id) => Resolution::User(linked_id),
+                None => Resolution::Refused("oidc_registration_conflict"),
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// --- Handlers ---
+
+#[get("/api/auth/oidc")]
+async fn oidc_info() -> impl Responder {
+    match config() {
+        Some(config) => HttpResponse::Ok().json(serde_json::json!({
+            "enabled": true,
+            "provider_name": config.provider_name,
+            "login_url": "/api/auth/oidc/login"
+        })),
+        None => HttpResponse::Ok().json(serde_json::json!({ "enabled": false })),
+    }
+}
+
+#[get("/api/auth/oidc/login")]
+async fn oidc_login(session: Session) -> impl Responder {
+    let Some(config) = config() else {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "OIDC login is disabled"}));
+    };
+
+    let authorization_endpoint = match &config.authorization_url {
+        Some(url) => url.clone(),
+        None => match config.endpoints().await {
+            Ok(endpoints) => endpoints.authorization_endpoint,
+            Err(e) => {
+                log::error!("Failed to discover OIDC endpoints: {}", e);
+                return login_error("oidc_provider_unavailable");
+            }
+        },
+    };
+
+    let state = random_string(32);
+    let nonce = random_string(32);
+    let verifier = random_string(64);
+    if let Err(e) = session
+        .insert(STATE_KEY, &state)
+        .and_then(|_| session.insert(NONCE_KEY, &nonce))
+        .and_then(|_| session.insert(VERIFIER_KEY, &verifier))
+    {
+        log::error!("Failed to store OIDC login state in session: {:?}", e);
+        return login_error("oidc_internal");
+    }
+
+    let params = [
+        ("response_type", "code"),
+        ("client_id", config.client_id.as_str()),
+        ("redirect_uri", config.redirect_url.as_str()),
+        ("scope", config.scopes.as_str()),
+        ("state", state.as_str()),
+        ("nonce", nonce.as_str()),
+        ("code_challenge", &pkce_challenge(&verifier)),
+        ("code_challenge_method", "S256"),
+    ];
+    let query = match serde_urlencoded::to_string(params) {
+        Ok(query) => query,
+        Err(e) => {
+            log::error!("Failed to encode OIDC authorization request: {:?}", e);
+            return login_error("oidc_internal");
+        }
+    };
+    let separator = if authorization_endpoint.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+
+    HttpResponse::Found()
+        .insert_header((
+            header::LOCATION,
+            format!("{}{}{}", authorization_endpoint, separator, query),
+        ))
+        .finish()
+}
+
+#[get("/api/auth/oidc/callback")]
+async fn oidc_callback(
+    pool: web::Data<SqlitePool>,
+    session: Session,
+    client: ClientInfo,
+    query: web::Query<CallbackQuery>,
+) -> impl Responder {
+    let Some(config) = config() else {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({"error": "OIDC login is disabled"}));
+    };
+
+    // The login state is single use, whatever happens next.
+    let expected_state = session.remove_as::<String>(STATE_KEY).and_then(Result::ok);
+    let nonce = session.remove_as::<String>(NONCE_KEY).and_then(Result::ok);
+    let verifier = session
+        .remove_as::<String>(VERIFIER_KEY)
+        .and_then(Result::ok);
+
+    if let Some(error) = &query.error {
+        log::warn!(
+            "OIDC provider returned error '{}': {:?}",
+            error,
+            query.error_description
+        );
+        return login_error("oidc_provider_error");
+    }
+    let (Some(expected_state), Some(nonce), Some(verifier)) = (expected_state, nonce, verifier)
+    else {
+        log::warn!("OIDC callback without a login in progress.");
+        return login_error("oidc_state_mismatch");
+    };
+    if query.state.as_deref() != Some(expected_state.as_str()) {
+        log::warn!("OIDC callback with mismatching state.");
+        return login_error("oidc_state_mismatch");
+    }
+    let Some(code) = query.code.as_deref() else {
+        return login_error("oidc_provider_error");
+    };
+
+    let endpoints = match config.endpoints().await {
+        Ok(endpoints) => endpoints,
+        Err(e) => {
+            log::error!("Failed to discover OIDC endpoints: {}", e);
+            return login_error("oidc_provider_unavailable");
+        }
+    };
+
+    let form = TokenRequest {
+        grant_type: "authorization_code",
+        code,
+        redirect_uri: &config.redirect_url,
+        client_id: &config.client_id,
+        code_verifier: &verifier,
+    };
+    let mut request = http_client().post(&endpoints.token_endpoint);
+    if let Some(secret) = &config.client_secret {
+        request = request.basic_auth(&config.client_id, secret);
+    }
+    let token = match request.send_form(&form).await {
+        Ok(mut response) if response.status().is_success() => {
+            match response.json::<TokenResponse>().await {
+                Ok(token) => token,
+                Err(e) => {
+                    log::error!("Invalid OIDC token response: {:?}", e);
+                    return login_error("oidc_token_error");
+                }
+            }
+        }
+        Ok(mut response) => {
+            let body = response.body().await.unwrap_or_default();
+            log::error!(
+                "OIDC token endpoint returned {}: {}",
+                response.status(),
+                String::from_utf8_lossy(&body)
+            );
+            return login_error("oidc_token_error");
+        }
+        Err(e) => {
+            log::error!("OIDC token request failed: {:?}", e);
+            return login_error("oidc_provider_unavailable");
+        }
+    };
+
+    let claims = match validate_id_token(config, &token.id_token, &nonce) {
+        Ok(claims) => claims,
+        Err(e) => {
+            log::warn!("Rejected OIDC ID token: {}", e);
+            return login_error("oidc_invalid_id_token");
+        }
+    };
+
+    let logged_in_user = session.get::<i64>("user_id").ok().flatten();
+    let user_id =
+        match resolve_identity(pool.get_ref(), config, &claims, logged_in_user, &client).await {
+            Ok(Resolution::User(user_id)) => user_id,
+            Ok(Resolution::Refused(code)) => {
+                log::warn!("OIDC login for subject '{}' refused: {}", claims.sub, code);
+                return login_error(code);
+            }
+            Err(e) => {
+                log::error!("Database error resolving OIDC identity: {:?}", e);
+                return login_error("oidc_internal");
+            }
+        };
+
+    let username = match sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(username) => username,
+        Err(e) => {
+            log::error!("Failed to load user {} after OIDC login: {:?}", user_id, e);
+            return login_error("oidc_internal");
+        }
+    };
+
+    if let Err(e) = sessions::start_login(&session, &client, user_id) {
+        log::error!("Failed to insert user_id into session: {:?}", e);
+        return login_error("oidc_internal");
+    }
+    audit::record(
+        pool.get_ref(),
+        &client,
+        AuthEvent::LoginSucceeded,
+        Some(user_id),
+        Some(&username),
+        Some(&format!("oidc {}", config.issuer)),
+    )
+    .await;
+    log::info!("User '{}' logged in through OIDC.", username);
+
+    HttpResponse::Found()
+        .insert_header((
+            header::LOCATION,
+            format!("{}/search.html", public_base_url()),
+        ))
+        .finish()
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(oidc_info)
+        .service(oidc_login)
+        .service(oidc_callback);
+}
@@ -0,0 +1,168 @@
+// backend/src/password.rs
+// --- Password Hashing ---
+// Argon2id with costs taken from the configuration. Verification reads the parameters
+// stored in each hash, so raising the costs never locks anyone out; `needs_rehash`
+// spots hashes made with older settings so `post_login` can upgrade them, and
+// `verify_dummy` gives logins for unknown usernames the same cost as real ones.
+// `verify_pow_solution` checks the work behind a proof-of-work challenge (see pow.rs).
+use std::sync::OnceLock;
+
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
+};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use whoknows_config::Argon2Settings;
+
+static PARAMS: OnceLock<Params> = OnceLock::new();
+// Hash of a random password, made with the configured costs, for `verify_dummy`.
+static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+/// Sets the Argon2 costs. Must run before the first hash is made; without it the
+/// crate defaults are used.
+pub fn init(settings: &Argon2Settings) -> Result<(), String> {
+    let params = Params::new(
+        settings.memory_kib,
+        settings.iterations,
+        settings.parallelism,
+        None,
+    )
+    .map_err(|e| format!("Invalid Argon2 parameters: {}", e))?;
+
+    log::info!(
+        "Argon2id parameters: m={} KiB, t={}, p={}",
+        params.m_cost(),
+        params.t_cost(),
+        params.p_cost()
+    );
+    PARAMS
+        .set(params)
+        .map_err(|_| "Argon2 parameters already initialised".to_string())?;
+    // Made now rather than on the first unknown-username login, which would stand out.
+    dummy_hash();
+    Ok(())
+}
+
+fn current_params() -> &'static Params {
+    PARAMS.get_or_init(Params::default)
+}
+
+fn argon2() -> Argon2<'static> {
+    Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        current_params().clone(),
+    )
+}
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = argon2()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string();
+    Ok(password_hash)
+}
+
+pub fn verify_password(
+    stored_hash: &str,
+    password_provided: &str,
+) -> Result<bool, argon2::password_hash::Error> {
+    let parsed_hash = PasswordHash::new(stored_hash)?;
+    Ok(Argon2::default()
+        .verify_password(password_provided.as_bytes(), &parsed_hash)
+        .is_ok())
+}
+
+fn dummy_hash() -> &'static str {
+    DUMMY_HASH.get_or_init(|| {
+        let mut password = [0u8; 32];
+        OsRng.fill_bytes(&mut password);
+        hash_password(&hex::encode(password)).expect("hashing a random password")
+    })
+}
+
+/// Does the work of `verify_password` for a user that doesn't exist, so a login for an
+/// unknown username takes as long as one with a wrong password.
+pub fn verify_dummy(password_provided: &str) {
+    let _ = verify_password(dummy_hash(), password_provided);
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+/// Whether SHA-256 of `challenge:solution` starts with `difficulty` zero bits.
+pub fn verify_pow_solution(challenge: &str, solution: u64, difficulty: u32) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", challenge, solution).as_bytes());
+    leading_zero_bits(&hash) >= difficulty
+}
+
+/// Whether `stored_hash` was made with another algorithm, version or cost than the
+/// configured one. Unparseable hashes count as outdated.
+pub fn needs_rehash(stored_hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(stored_hash) else {
+        return true;
+    };
+    if parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    let wanted = current_params();
+    match Params::try_from(&parsed) {
+        Ok(params) => {
+            params.m_cost() != wanted.m_cost()
+                || params.t_cost() != wanted.t_cost()
+                || params.p_cost() != wanted.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Re-hashes a just-verified password when its stored hash is outdated. Errors are
+/// logged only; the login itself already succeeded.
+pub async fn rehash_if_needed(pool: &SqlitePool, user_id: i64, stored_hash: &str, password: &str) {
+    if !needs_rehash(stored_hash) {
+        return;
+    }
+
+    let new_hash = match hash_password(password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::error!("Failed to rehash password for user {}: {:?}", user_id, e);
+            return;
+        }
+    };
+
+    // Only replace the hash that was verified, in case the password changed meanwhile.
+    match sqlx::query("UPDATE users SET password = ? WHERE id = ? AND password = ?")
+        .bind(&new_hash)
+        .bind(user_id)
+        .bind(stored_hash)
+        .execute(pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 1 => {
+            log::info!("Upgraded password hash parameters for user {}.", user_id);
+        }
+        Ok(_) => {}
+        Err(e) => log::error!(
+            "Failed to store rehashed password for user {}: {:?}",
+            user_id,
+            e
+        ),
+    }
+}
@@ -0,0 +1,396 @@
+// backend/src/policy.rs
+// --- Password Policy ---
+// Rules every new password must pass: a minimum length, a minimum strength score, no
+// username or email inside it, and absence from the local breached-password set.
+// Violations carry a stable `code` so clients can show their own messages.
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use actix_web::{web, HttpResponse};
+use sha1::{Digest, Sha1};
+use whoknows_config::PasswordPolicySettings;
+
+// Parts of a password that are too predictable to count towards its strength.
+const COMMON_FRAGMENTS: &[&str] = &[
+    "password", "passw0rd", "qwerty", "azerty", "asdf", "letmein", "welcome", "admin", "login",
+    "monkey", "dragon", "master", "secret", "iloveyou", "whoknows", "abc", "123",
+];
+
+struct Policy {
+    min_length: usize,
+    min_strength: u8,
+    breached: Option<BreachedSet>,
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    TooShort(usize),
+    TooWeak,
+    ContainsUsername,
+    ContainsEmail,
+    Breached,
+}
+
+impl PolicyViolation {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PolicyViolation::TooShort(_) => "password_too_short",
+            PolicyViolation::TooWeak => "password_too_weak",
+            PolicyViolation::ContainsUsername => "password_contains_username",
+            PolicyViolation::ContainsEmail => "password_contains_email",
+            PolicyViolation::Breached => "password_breached",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            PolicyViolation::TooShort(min) => {
+                format!("Password must be at least {} characters long", min)
+            }
+            PolicyViolation::TooWeak => {
+                "Password is too easy to guess, use a longer or less predictable one".to_string()
+            }
+            PolicyViolation::ContainsUsername => {
+                "Password must not contain your username".to_string()
+            }
+            PolicyViolation::ContainsEmail => {
+                "Password must not contain your email address".to_string()
+            }
+            PolicyViolation::Breached => {
+                "Password appears in a list of breached passwords, choose another".to_string()
+            }
+        }
+    }
+
+    /// 400 response in the shape every endpoint taking a new password uses.
+    pub fn to_response(self) -> HttpResponse {
+        HttpResponse::BadRequest().json(serde_json::json!({
+            "error": self.message(),
+            "code": self.code()
+        }))
+    }
+}
+
+fn breached_prefix(password: &str) -> u64 {
+    let digest = Sha1::digest(password.as_bytes());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix)
+}
+
+/// The breached-password set: big-endian `u64` prefixes of SHA-1 hashes, sorted, as
+/// written by `scripts/build_breached_set.py`. It stays on disk and is binary searched
+/// with one 8-byte read per step, since a real breach corpus runs to gigabytes.
+struct BreachedSet {
+    file: Mutex<File>,
+    count: u64,
+}
+
+impl BreachedSet {
+    /// Opens the file and checks, in one streaming pass, that it is a sorted prefix set.
+    fn open(path: &Path) -> Result<BreachedSet, String> {
+        let failed = |e: io::Error| {
+            format!(
+                "Failed to read breached-password file '{}': {}",
+                path.display(),
+                e
+            )
+        };
+        let file = File::open(path).map_err(failed)?;
+        let len = file.metadata().map_err(failed)?.len();
+        if len % 8 != 0 {
+            return Err(format!(
+                "Breached-password file '{}' is not a prefix set (length {} is not a multiple of 8)",
+                path.display(),
+                len
+            ));
+        }
+
+        let mut reader = BufReader::with_capacity(1 << 16, &file);
+        let mut previous = 0u64;
+        let mut entry = [0u8; 8];
+        for index in 0..len / 8 {
+            reader.read_exact(&mut entry).map_err(failed)?;
+            let prefix = u64::from_be_bytes(entry);
+            if prefix < previous {
+                return Err(format!(
+                    "Breached-password file '{}' is not sorted (entry {}); rebuild it with \
+                     scripts/build_breached_set.py",
+                    path.display(),
+                    index
+                ));
+            }
+            previous = prefix;
+        }
+        drop(reader);
+
+        Ok(BreachedSet {
+            file: Mutex::new(file),
+            count: len / 8,
+        })
+    }
+
+    fn contains(&self, prefix: u64) -> io::Result<bool> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entry = [0u8; 8];
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            file.seek(SeekFrom::Start(mid * 8))?;
+            file.read_exact(&mut entry)?;
+            match u64::from_be_bytes(entry).cmp(&prefix) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Sets the policy from the configuration and loads the breached-password set. Without
+/// it the defaults apply and the breached-password check is off.
+pub fn init(settings: &PasswordPolicySettings) -> Result<(), String> {
+    let breached = match &settings.breached_file {
+        Some(path) => Some(BreachedSet::open(path)?),
+        None => {
+            log::warn!("No breached-password file configured, breached-password check disabled.");
+            None
+        }
+    };
+
+    log::info!(
+        "Password policy: min length {}, min strength {}, {} breached hashes on disk.",
+        settings.min_length,
+        settings.min_strength,
+        breached.as_ref().map_or(0, |set| set.count)
+    );
+    POLICY
+        .set(Policy {
+            min_length: settings.min_length,
+            min_strength: settings.min_strength,
+            breached,
+        })
+        .map_err(|_| "Password policy already initialised".to_string())
+}
+
+fn policy() -> &'static Policy {
+    POLICY.get_or_init(|| {
+        let defaults = PasswordPolicySettings::default();
+        Policy {
+            min_length: defaults.min_length,
+            min_strength: defaults.min_strength,
+            breached: None,
+        }
+    })
+}
+
+/// Rough strength score from 0 (trivial) to 4 (strong), on the same scale as zxcvbn.
+///
+/// Estimates entropy from the character classes used, after discounting repeated
+/// characters, alphabet and digit runs, common fragments and the user's own inputs.
+fn strength(password: &str, user_inputs: &[&str]) -> u8 {
+    let lower = password.to_lowercase();
+    let chars: Vec<char> = lower.chars().collect();
+
+    let mut pool = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+
+    // Characters that repeat or continue a run from the previous one add little.
+    let mut effective = 0.0f64;
+    for (i, c) in chars.iter().enumerate() {
+        let predictable = i > 0 && {
+            let prev = chars[i - 1] as i64;
+            let cur = *c as i64;
+            (cur - prev).abs() <= 1
+        };
+        effective += if predictable { 0.25 } else { 1.0 };
+    }
+
+    // A known fragment is worth about one character, however long it is.
+    let mut seen = HashSet::new();
+    for fragment in COMMON_FRAGMENTS
+        .iter()
+        .copied()
+        .chain(user_inputs.iter().copied().filter(|s| s.len() >= 3))
+    {
+        let fragment = fragment.to_lowercase();
+        if lower.contains(&fragment) && seen.insert(fragment.clone()) {
+            let len = fragment.chars().count() as f64;
+            effective -= (len - 1.0).max(0.0) * 0.75;
+        }
+    }
+
+    let bits = effective.max(0.0) * f64::from(pool.max(1)).log2();
+    match bits {
+        b if b < 25.0 => 0,
+        b if b < 40.0 => 1,
+        b if b < 55.0 => 2,
+        b if b < 70.0 => 3,
+        _ => 4,
+    }
+}
+
+async fn is_breached(password: &str) -> bool {
+    let Some(breached) = &policy().breached else {
+        return false;
+    };
+    let prefix = breached_prefix(password);
+    // The search blocks on disk reads and the file's lock, so it runs off the workers. A
+    // broken file shouldn't lock everyone out of changing their password.
+    match web::block(move || breached.contains(prefix)).await {
+        Ok(Ok(found)) => found,
+        Ok(Err(e)) => {
+            log::error!("Failed to read the breached-password file: {:?}", e);
+            false
+        }
+        Err(e) => {
+            log::error!("Failed to search the breached-password file: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Checks a new password against the policy. `username` and `email` belong to the
+/// account the password is for.
+pub async fn check(password: &str, username: &str, email: &str) -> Result<(), PolicyViolation> {
+    let policy = policy();
+    let lower = password.to_lowercase();
+    let username = username.trim().to_lowercase();
+    let email = email.trim().to_lowercase();
+    let email_local = email.split('@').next().unwrap_or_default();
+
+    if password.chars().count() < policy.min_length {
+        return Err(PolicyViolation::TooShort(policy.min_length));
+    }
+    if username.len() >= 3 && lower.contains(&username) {
+        return Err(PolicyViolation::ContainsUsername);
+    }
+    if email_local.len() >= 3 && lower.contains(email_local) {
+        return Err(PolicyViolation::ContainsEmail);
+    }
+    if is_breached(password).await {
+        return Err(PolicyViolation::Breached);
+    }
+    if strength(password, &[&username, email_local]) < policy.min_strength {
+        return Err(PolicyViolation::TooWeak);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Writes `prefixes` as a breached-password file unique to this test.
+    fn prefix_file(name: &str, prefixes: &[u64]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "whoknows-breached-{}-{}.bin",
+            std::process::id(),
+            name
+        ));
+        let bytes: Vec<u8> = prefixes.iter().flat_map(|p| p.to_be_bytes()).collect();
+        std::fs::write(&path, bytes).expect("fixture is written");
+        path
+    }
+
+    #[test]
+    fn breached_set_finds_every_entry_and_nothing_else() {
+        let entries = [0x10, 0x20, 0x2f, 0x30, u64::MAX];
+        let path = prefix_file("sorted", &entries);
+        let set = BreachedSet::open(&path).expect("sorted set opens");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(set.count, 5);
+        for prefix in entries {
+            assert!(set.contains(prefix).unwrap(), "{:#x} not found", prefix);
+        }
+        for prefix in [0, 0x0f, 0x11, 0x2e, 0x31, u64::MAX - 1] {
+            assert!(!set.contains(prefix).unwrap(), "{:#x} found", prefix);
+        }
+    }
+
+    #[test]
+    fn breached_set_matches_sha1_prefixes() {
+        let path = prefix_file("sha1", &[breached_prefix("hunter2")]);
+        let set = BreachedSet::open(&path).expect("set opens");
+        std::fs::remove_file(&path).ok();
+
+        assert!(set.contains(breached_prefix("hunter2")).unwrap());
+        assert!(!set.contains(breached_prefix("hunter3")).unwrap());
+    }
+
+    #[test]
+    fn breached_set_rejects_unsorted_and_truncated_files() {
+        let path = prefix_file("unsorted", &[0x10, 0x30, 0x20]);
+        let unsorted = BreachedSet::open(&path).err();
+        std::fs::remove_file(&path).ok();
+        assert!(unsorted
+            .expect("unsorted set is rejected")
+            .contains("not sorted (entry 2)"));
+
+        let path = prefix_file("truncated", &[]);
+        std::fs::write(&path, [0u8; 12]).unwrap();
+        let truncated = BreachedSet::open(&path).err();
+        std::fs::remove_file(&path).ok();
+        assert!(truncated
+            .expect("truncated set is rejected")
+            .contains("multiple of 8"));
+    }
+
+    #[test]
+    fn strength_scores_change_at_the_thresholds() {
+        // Lowercase only, no runs, repeats or fragments: about 4.7 bits per character.
+        let random = "kqxmvrtzpwjgnhd";
+        let cases = [
+            (5, 0),  // 23.5 bits, below 25
+            (6, 1),  // 28.2
+            (8, 1),  // 37.6, below 40
+            (9, 2),  // 42.3
+            (11, 2), // 51.7, below 55
+            (12, 3), // 56.4
+            (14, 3), // 65.8, below 70
+            (15, 4), // 70.5
+        ];
+        for (len, score) in cases {
+            assert_eq!(strength(&random[..len], &[]), score, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn strength_discounts_predictable_parts() {
+        let cases = [
+            ("aaaaaaaaaaaaaaa", &[][..], 0),
+            ("abcdefghijklmno", &[][..], 0),
+            ("Password1234!", &[][..], 0),
+            // Scores 4 on its own, see above.
+            ("kqxmvrtzpwjgnhd", &["kqxmvrtzpwjg"][..], 1),
+        ];
+        for (password, user_inputs, score) in cases {
+            assert_eq!(strength(password, user_inputs), score, "{}", password);
+        }
+    }
+}
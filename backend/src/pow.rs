@@ -0,0 +1,303 @@
+// backend/src/pow.rs
+// --- Proof of Work ---
+// Self-hosted bot brake for registration and repeated logins. The backend hands out
+// HMAC-signed challenges; the client must find a number whose SHA-256 together with
+// the challenge starts with `difficulty` zero bits. Difficulty grows with the recent
+// number of failed logins and lockouts, so abuse makes itself slower. Used challenges
+// are kept in the database, so instances sharing `POW_SECRET` also share them.
+use std::sync::OnceLock;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use whoknows_config::PowSettings;
+
+use crate::audit::AuthEvent;
+use crate::csrf::tokens_match;
+use crate::password::verify_pow_solution;
+
+const CHALLENGE_TTL_SECS: i64 = 300;
+// Window and step for the abuse-based difficulty: one extra bit each time the number
+// of events in the window doubles past the step.
+const ABUSE_WINDOW: &str = "-10 minutes";
+const ABUSE_STEP: i64 = 20;
+
+struct PowConfig {
+    enabled: bool,
+    secret: Vec<u8>,
+    base_difficulty: u32,
+    max_difficulty: u32,
+    login_after_failures: i64,
+}
+
+static CONFIG: OnceLock<PowConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    Login,
+    Register,
+}
+
+impl Purpose {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Purpose::Login => "login",
+            Purpose::Register => "register",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "login" => Some(Purpose::Login),
+            "register" => Some(Purpose::Register),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowError {
+    Required,
+    Invalid,
+    Expired,
+    Reused,
+    Unavailable,
+}
+
+impl PowError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PowError::Required => "pow_required",
+            PowError::Invalid => "pow_invalid",
+            PowError::Expired => "pow_expired",
+            PowError::Reused => "pow_reused",
+            PowError::Unavailable => "pow_unavailable",
+        }
+    }
+
+    /// 428 when a solution is missing, 400 when the one sent doesn't hold up, 500 when
+    /// it couldn't be recorded. Either way the client fetches a new challenge from
+    /// `/api/pow/challenge`.
+    pub fn to_response(self) -> HttpResponse {
+        let message = match self {
+            PowError::Required => "A proof-of-work solution is required",
+            PowError::Invalid => "Invalid proof-of-work solution",
+            PowError::Expired => "The proof-of-work challenge has expired",
+            PowError::Reused => "The proof-of-work challenge was already used",
+            PowError::Unavailable => "Failed to check the proof-of-work solution",
+        };
+        let body = serde_json::json!({ "error": message, "code": self.code() });
+        match self {
+            PowError::Required => {
+                HttpResponse::build(actix_web::http::StatusCode::PRECONDITION_REQUIRED).json(body)
+            }
+            PowError::Unavailable => HttpResponse::InternalServerError().json(body),
+            _ => HttpResponse::BadRequest().json(body),
+        }
+    }
+}
+
+/// Sets up proof of work from the configuration. Without a configured secret a
+/// random key is used, so outstanding challenges die with the process.
+pub fn init(settings: &PowSettings) -> Result<(), String> {
+    let secret = match &settings.secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            let mut bytes = vec![0u8; 32];
+            OsRng.fill_bytes(&mut bytes);
+            bytes
+        }
+    };
+
+    if settings.enabled {
+        log::info!(
+            "Proof of work enabled: difficulty {}-{} bits, logins after {} failures.",
+            settings.base_difficulty,
+            settings.max_difficulty,
+            settings.login_after_failures
+        );
+    } else {
+        log::warn!("Proof of work disabled.");
+    }
+    CONFIG
+        .set(PowConfig {
+            enabled: settings.enabled,
+            secret,
+            base_difficulty: settings.base_difficulty,
+            max_difficulty: settings.max_difficulty,
+            login_after_failures: settings.login_after_failures,
+        })
+        .map_err(|_| "Proof-of-work configuration already initialised".to_string())
+}
+
+fn config() -> &'static PowConfig {
+    CONFIG.get_or_init(|| {
+        let defaults = PowSettings::default();
+        PowConfig {
+            enabled: false,
+            secret: Vec::new(),
+            base_difficulty: defaults.base_difficulty,
+            max_difficulty: defaults.max_difficulty,
+            login_after_failures: defaults.login_after_failures,
+        }
+    })
+}
+
+/// Whether a login after `failures` recent failures for the username needs a solution.
+pub fn required_for_login(failures: i64) -> bool {
+    let config = config();
+    config.enabled && failures >= config.login_after_failures
+}
+
+pub fn required_for_registration() -> bool {
+    config().enabled
+}
+
+fn sign(payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(&config().secret).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+async fn current_difficulty(pool: &SqlitePool) -> u32 {
+    let config = config();
+    let recent = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM auth_events
+         WHERE event_type IN (?, ?) AND created_at > datetime('now', ?)",
+    )
+    .bind(AuthEvent::LoginFailed.as_str())
+    .bind(AuthEvent::Lockout.as_str())
+    .bind(ABUSE_WINDOW)
+    .fetch_one(pool)
+    .await
+    .unwrap_or_else(|e| {
+        log::error!("Failed to count recent auth events for PoW: {:?}", e);
+        0
+    });
+
+    let extra = (recent / ABUSE_STEP + 1).ilog2();
+    (config.base_difficulty + extra).min(config.max_difficulty)
+}
+
+/// Checks a solution for `purpose` and marks its challenge as used.
+///
+/// A challenge has the form `purpose.difficulty.expires.nonce.signature`; the
+/// solution is a decimal number `n` such that SHA-256 of `challenge:n` starts with
+/// `difficulty` zero bits.
+pub async fn verify(
+    pool: &SqlitePool,
+    challenge: Option<&str>,
+    solution: Option<&str>,
+    purpose: Purpose,
+) -> Result<(), PowError> {
+    let (Some(challenge), Some(solution)) = (challenge, solution) else {
+        return Err(PowError::Required);
+    };
+
+    let (payload, signature) = challenge.rsplit_once('.').ok_or(PowError::Invalid)?;
+    if !tokens_match(&sign(payload), signature) {
+        return Err(PowError::Invalid);
+    }
+
+    let mut parts = payload.split('.');
+    let (Some(kind), Some(difficulty), Some(expires), Some(nonce), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return Err(PowError::Invalid);
+    };
+    if Purpose::parse(kind) != Some(purpose) {
+        return Err(PowError::Invalid);
+    }
+    let difficulty: u32 = difficulty.parse().map_err(|_| PowError::Invalid)?;
+    let expires: i64 = expires.parse().map_err(|_| PowError::Invalid)?;
+    let now = chrono::Utc::now().timestamp();
+    if expires < now {
+        return Err(PowError::Expired);
+    }
+
+    let solution: u64 = solution.trim().parse().map_err(|_| PowError::Invalid)?;
+    if !verify_pow_solution(challenge, solution, difficulty) {
+        return Err(PowError::Invalid);
+    }
+
+    // Expired nonces can't be replayed anyway, so they go as new ones come in.
+    if let Err(e) = sqlx::query("DELETE FROM pow_used_nonces WHERE expires_at < ?")
+        .bind(now)
+        .execute(pool)
+        .await
+    {
+        log::error!("Failed to delete expired PoW nonces: {:?}", e);
+    }
+    match sqlx::query(
+        "INSERT INTO pow_used_nonces (nonce, expires_at) VALUES (?, ?)
+         ON CONFLICT (nonce) DO NOTHING",
+    )
+    .bind(nonce)
+    .bind(expires)
+    .execute(pool)
+    .await
+    {
+        Ok(result) if result.rows_affected() == 1 => Ok(()),
+        Ok(_) => Err(PowError::Reused),
+        Err(e) => {
+            log::error!("Failed to record used PoW nonce: {:?}", e);
+            Err(PowError::Unavailable)
+        }
+    }
+}
+
+// --- Handlers ---
+
+#[derive(Deserialize, Debug)]
+struct ChallengeQuery {
+    purpose: String,
+}
+
+#[get("/api/pow/challenge")]
+async fn get_challenge(
+    pool: web::Data<SqlitePool>,
+    query: web::Query<ChallengeQuery>,
+) -> impl Responder {
+    let Some(purpose) = Purpose::parse(&query.purpose) else {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "purpose must be login or register"}));
+    };
+    if !config().enabled {
+        return HttpResponse::Ok().json(serde_json::json!({ "enabled": false }));
+    }
+
+    let difficulty = current_difficulty(pool.get_ref()).await;
+    let expires = chrono::Utc::now().timestamp() + CHALLENGE_TTL_SECS;
+    let mut nonce = [0u8; 16];
+    OsRng.fill_bytes(&mut nonce);
+    let payload = format!(
+        "{}.{}.{}.{}",
+        purpose.as_str(),
+        difficulty,
+        expires,
+        hex::encode(nonce)
+    );
+    let challenge = format!("{}.{}", payload, sign(&payload));
+
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(serde_json::json!({
+            "enabled": true,
+            "challenge": challenge,
+            "difficulty": difficulty,
+            "expires_at": expires,
+            "algorithm": "sha256-leading-zero-bits"
+        }))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_challenge);
+}
@@ -0,0 +1,357 @@
+// backend/src/rbac.rs
+// --- Role-Based Access Control ---
+// Every user has one role; roles map to permissions in the `role_permissions` table.
+// Routes declare what they need with `RequirePermission`, either per route through the
+// `wrap = "..."` argument of the route macros or on a whole `web::scope`.
+
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::StatusCode;
+use actix_web::{get, put, web, Error, HttpMessage, HttpResponse, Responder};
+use futures::future::{self, LocalBoxFuture, Ready};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+use crate::audit::{self, AuthEvent, ClientInfo};
+use crate::auth::{json_error, resolve_user, AuthenticatedUser};
+
+// --- Permissions ---
+pub const SEARCH_READ: &str = "search:read";
+pub const TOKENS_MANAGE: &str = "tokens:manage";
+pub const ACCOUNT_MANAGE: &str = "account:manage";
+pub const USERS_MANAGE: &str = "users:manage";
+pub const AUDIT_READ: &str = "audit:read";
+pub const SYSTEM_READ: &str = "system:read";
+
+pub const ALL_PERMISSIONS: &[&str] = &[
+    SEARCH_READ,
+    TOKENS_MANAGE,
+    ACCOUNT_MANAGE,
+    USERS_MANAGE,
+    AUDIT_READ,
+    SYSTEM_READ,
+];
+
+pub const ROLE_ADMIN: &str = "admin";
+
+static INITIAL_ADMIN: OnceLock<Option<String>> = OnceLock::new();
+
+/// Remembers the `INITIAL_ADMIN` username; call once at startup.
+pub fn init(initial_admin: Option<&str>) -> Result<(), String> {
+    INITIAL_ADMIN
+        .set(initial_admin.map(str::to_string))
+        .map_err(|_| "Initial admin already initialised".to_string())
+}
+
+/// Gives the `INITIAL_ADMIN` user the admin role if nobody has it yet. Runs at startup
+/// and after every registration, so the first admin can also sign up later.
+pub async fn promote_initial_admin(pool: &SqlitePool) {
+    let Some(Some(username)) = INITIAL_ADMIN.get() else {
+        return;
+    };
+    let promoted = sqlx::query_scalar::<_, i64>(
+        "UPDATE users SET role = ?1 WHERE username = ?2
+             AND NOT EXISTS (SELECT 1 FROM users WHERE role = ?1)
+         RETURNING id",
+    )
+    .bind(ROLE_ADMIN)
+    .bind(username)
+    .fetch_optional(pool)
+    .await;
+    match promoted {
+        Ok(Some(user_id)) => {
+            audit::record(
+                pool,
+                &ClientInfo {
+                    ip: None,
+                    user_agent: None,
+                },
+                AuthEvent::RoleChanged,
+                Some(user_id),
+                Some(username),
+                Some("set to 'admin' as INITIAL_ADMIN"),
+            )
+            .await;
+            log::info!("Gave initial admin '{}' the admin role.", username);
+        }
+        Ok(None) => {}
+        Err(e) => log::error!("Failed to promote initial admin '{}': {:?}", username, e),
+    }
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct RoleRow {
+    name: String,
+    description: String,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct UserRoleRow {
+    id: i64,
+    username: String,
+    email: String,
+    role: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SetRoleForm {
+    role: String,
+}
+
+/// Loads the permissions granted to `role`.
+pub async fn role_permissions(pool: &SqlitePool, role: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT permission FROM role_permissions WHERE role = ? ORDER BY permission",
+    )
+    .bind(role)
+    .fetch_all(pool)
+    .await
+}
+
+// --- Authorization Middleware ---
+
+/// Rejects requests whose caller lacks `permission` with 401 (not authenticated) or
+/// 403 (authenticated but not allowed). On success the resolved user is stored in the
+/// request extensions, so the `AuthenticatedUser` extractor doesn't query it again.
+pub struct RequirePermission {
+    permission: &'static str,
+}
+
+impl RequirePermission {
+    pub fn new(permission: &'static str) -> Self {
+        RequirePermission { permission }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(RequirePermissionMiddleware {
+            service: Rc::new(service),
+            permission: self.permission,
+        })
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: Rc<S>,
+    permission: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let permission = self.permission;
+
+        Box::pin(async move {
+            let user = match resolve_user(req.request()).await {
+                Ok(Some(user)) => user,
+                Ok(None) => {
+                    let err = json_error(StatusCode::UNAUTHORIZED, "Authentication required");
+                    return Ok(req.error_response(err).map_into_right_body());
+                }
+                Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+            };
+
+            if !user.has_permission(permission) {
+                log::warn!(
+                    "User '{}' (role '{}') denied access to {}: missing permission '{}'.",
+                    user.username,
+                    user.role,
+                    req.path(),
+                    permission
+                );
+                let err = json_error(StatusCode::FORBIDDEN, "Forbidden");
+                return Ok(req.error_response(err).map_into_right_body());
+            }
+
+            req.extensions_mut().insert(user);
+            let res = service.call(req).await?;
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+// --- Handlers ---
+
+#[get("/api/admin/roles", wrap = "RequirePermission::new(USERS_MANAGE)")]
+async fn list_roles(pool: web::Data<SqlitePool>) -> impl Responder {
+    let roles =
+        match sqlx::query_as::<_, RoleRow>("SELECT name, description FROM roles ORDER BY name")
+            .fetch_all(pool.get_ref())
+            .await
+        {
+            Ok(roles) => roles,
+            Err(e) => {
+                log::error!("Failed to list roles: {:?}", e);
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error listing roles"}));
+            }
+        };
+
+    let mut result = Vec::with_capacity(roles.len());
+    for role in roles {
+        match role_permissions(pool.get_ref(), &role.name).await {
+            Ok(permissions) => result.push(serde_json::json!({
+                "name": role.name,
+                "description": role.description,
+                "permissions": permissions
+            })),
+            Err(e) => {
+                log::error!(
+                    "Failed to load permissions for role '{}': {:?}",
+                    role.name,
+                    e
+                );
+                return HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error listing roles"}));
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({ "roles": result }))
+}
+
+#[get("/api/admin/users", wrap = "RequirePermission::new(USERS_MANAGE)")]
+async fn list_users(pool: web::Data<SqlitePool>) -> impl Responder {
+    match sqlx::query_as::<_, UserRoleRow>(
+        "SELECT id, username, email, role FROM users ORDER BY id",
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(users) => HttpResponse::Ok().json(serde_json::json!({ "users": users })),
+        Err(e) => {
+            log::error!("Failed to list users: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error listing users"}))
+        }
+    }
+}
+
+/// Why a role change was refused.
+pub enum RoleChangeError {
+    UnknownRole,
+    UserNotFound,
+    LastAdmin,
+    Database(sqlx::Error),
+}
+
+/// Gives user `user_id` the role `role`, refusing to demote the last admin so the
+/// instance is never left without anyone able to manage users.
+pub async fn change_role(
+    pool: &SqlitePool,
+    user_id: i64,
+    role: &str,
+) -> Result<(), RoleChangeError> {
+    let mut tx = pool.begin().await.map_err(RoleChangeError::Database)?;
+
+    let known = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM roles WHERE name = ?")
+        .bind(role)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(RoleChangeError::Database)?;
+    if known == 0 {
+        return Err(RoleChangeError::UnknownRole);
+    }
+
+    let updated = sqlx::query("UPDATE users SET role = ? WHERE id = ?")
+        .bind(role)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(RoleChangeError::Database)?;
+    if updated.rows_affected() == 0 {
+        return Err(RoleChangeError::UserNotFound);
+    }
+
+    let admins = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE role = ?")
+        .bind(ROLE_ADMIN)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(RoleChangeError::Database)?;
+    if admins == 0 {
+        return Err(RoleChangeError::LastAdmin);
+    }
+
+    tx.commit().await.map_err(RoleChangeError::Database)
+}
+
+#[put(
+    "/api/admin/users/{id}/role",
+    wrap = "RequirePermission::new(USERS_MANAGE)"
+)]
+async fn set_user_role(
+    pool: web::Data<SqlitePool>,
+    admin: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<i64>,
+    payload: web::Json<SetRoleForm>,
+) -> impl Responder {
+    let user_id = path.into_inner();
+    let role = payload.into_inner().role;
+
+    if let Err(e) = change_role(pool.get_ref(), user_id, &role).await {
+        return match e {
+            RoleChangeError::UnknownRole => HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": format!("Unknown role '{}'", role)})),
+            RoleChangeError::UserNotFound => {
+                HttpResponse::NotFound().json(serde_json::json!({"error": "User not found"}))
+            }
+            RoleChangeError::LastAdmin => HttpResponse::Conflict()
+                .json(serde_json::json!({"error": "Cannot remove the last admin"})),
+            RoleChangeError::Database(e) => {
+                log::error!("Failed to set role for user {}: {:?}", user_id, e);
+                HttpResponse::InternalServerError()
+                    .json(serde_json::json!({"error": "Database error changing role"}))
+            }
+        };
+    }
+
+    audit::record(
+        pool.get_ref(),
+        &client,
+        AuthEvent::RoleChanged,
+        Some(user_id),
+        None,
+        Some(&format!("set to '{}' by user {}", role, admin.id)),
+    )
+    .await;
+    log::info!(
+        "User '{}' set role of user {} to '{}'.",
+        admin.username,
+        user_id,
+        role
+    );
+    HttpResponse::Ok().json(serde_json::json!({"message": "Role updated", "role": role}))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_roles)
+        .service(list_users)
+        .service(set_user_role);
+}
@@ -0,0 +1,283 @@
+// backend/src/registration.rs
+// --- Registration Modes and Invites ---
+// The registration mode decides who may create an account: anyone (`open`), only
+// holders of an invite code (`invite-only`), or nobody (`closed`). Admins issue invite
+// codes with an expiry and a maximum number of uses; only a hash of each code is
+// stored. With uniform responses a taken username or email gets the same answer as a
+// successful registration, and the details go to the given address by mail.
+use std::sync::OnceLock;
+
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Sqlite, SqlitePool, Transaction};
+use whoknows_config::RegistrationSettings;
+
+use crate::account::public_base_url;
+use crate::auth::AuthenticatedUser;
+use crate::mail;
+use crate::rbac::{RequirePermission, USERS_MANAGE};
+use crate::tokens::hash_token;
+
+pub use whoknows_config::RegistrationMode;
+
+const INVITE_PREFIX: &str = "inv_";
+const DEFAULT_INVITE_DAYS: i64 = 7;
+const MAX_INVITE_DAYS: i64 = 365;
+
+static MODE: OnceLock<RegistrationMode> = OnceLock::new();
+static UNIFORM_RESPONSES: OnceLock<bool> = OnceLock::new();
+
+/// Sets the registration mode from the configuration; without it registration is
+/// open and answers conflicts with 409.
+pub fn init(settings: &RegistrationSettings) -> Result<(), String> {
+    log::info!(
+        "Registration mode: {}{}",
+        settings.mode.as_str(),
+        if settings.uniform_responses {
+            ", uniform responses"
+        } else {
+            ""
+        }
+    );
+    MODE.set(settings.mode)
+        .map_err(|_| "Registration mode already initialised".to_string())?;
+    UNIFORM_RESPONSES
+        .set(settings.uniform_responses)
+        .map_err(|_| "Registration mode already initialised".to_string())
+}
+
+pub fn mode() -> RegistrationMode {
+    *MODE.get_or_init(|| RegistrationMode::Open)
+}
+
+pub fn uniform_responses() -> bool {
+    *UNIFORM_RESPONSES.get_or_init(|| false)
+}
+
+/// The answer to every registration that got past validation when uniform responses
+/// are on, whether or not an account was created.
+pub fn uniform_response() -> HttpResponse {
+    HttpResponse::Accepted().json(serde_json::json!({
+        "success": true,
+        "message": "Registration received, check your email to continue"
+    }))
+}
+
+/// The answer to a uniformly answered registration whose mail couldn't be sent. It is
+/// the same whether or not an account was created, so it doesn't tell either.
+pub fn uniform_mail_failed_response() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().json(serde_json::json!({
+        "error": "Registration received, but the email about it could not be sent; try again later"
+    }))
+}
+
+/// Tells the owner of `email` what became of a registration that was answered
+/// uniformly. `existing_email` is the address of the account that was in the way.
+pub async fn notify_outcome(
+    username: &str,
+    email: &str,
+    existing_email: Option<&str>,
+) -> Result<(), mail::MailError> {
+    let login_url = format!("{}/login.html", public_base_url());
+    let (subject, body) = match existing_email {
+        None => (
+            "Welcome to WhoKnows",
+            format!(
+                "Your WhoKnows account '{}' is ready. Log in at {}",
+                username, login_url
+            ),
+        ),
+        Some(existing) if existing.eq_ignore_ascii_case(email) => (
+            "You already have a WhoKnows account",
+            format!(
+                "Someone tried to register a new WhoKnows account with this address, \
+                 which already has one. Log in at {} instead. \
+                 If this wasn't you, you can ignore this message.",
+                login_url
+            ),
+        ),
+        Some(_) => (
+            "Your WhoKnows registration",
+            format!(
+                "The username '{}' is already taken, so no account was created. \
+                 Register again with another username.",
+                username
+            ),
+        ),
+    };
+    mail::send(email, subject, &body).await
+}
+
+/// Uses up one redemption of `code` inside `tx`, so the count only sticks if the
+/// account is created too. Returns the invite id, or `None` for a code that is
+/// unknown, expired, revoked or used up.
+pub async fn redeem_invite(
+    tx: &mut Transaction<'_, Sqlite>,
+    code: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "UPDATE invites SET use_count = use_count + 1
+         WHERE code_hash = ?
+           AND revoked_at IS NULL
+           AND expires_at > CURRENT_TIMESTAMP
+           AND use_count < max_uses
+         RETURNING id",
+    )
+    .bind(hash_token(code.trim()))
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct Invite {
+    id: i64,
+    code_prefix: String,
+    note: String,
+    max_uses: i64,
+    use_count: i64,
+    created_by: Option<i64>,
+    created_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateInviteForm {
+    #[serde(default = "default_max_uses")]
+    max_uses: i64,
+    expires_in_days: Option<i64>,
+    #[serde(default)]
+    note: String,
+}
+
+fn default_max_uses() -> i64 {
+    1
+}
+
+fn generate_invite_code() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", INVITE_PREFIX, hex::encode(bytes))
+}
+
+// --- Handlers ---
+
+#[get("/api/registration")]
+async fn registration_info() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "mode": mode().as_str() }))
+}
+
+#[post("/api/admin/invites", wrap = "RequirePermission::new(USERS_MANAGE)")]
+async fn create_invite(
+    pool: web::Data<SqlitePool>,
+    admin: AuthenticatedUser,
+    payload: web::Json<CreateInviteForm>,
+) -> impl Responder {
+    let form = payload.into_inner();
+    if form.max_uses < 1 {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "max_uses must be at least 1"}));
+    }
+    let days = form.expires_in_days.unwrap_or(DEFAULT_INVITE_DAYS);
+    if !(1..=MAX_INVITE_DAYS).contains(&days) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("expires_in_days must be between 1 and {}", MAX_INVITE_DAYS)
+        }));
+    }
+
+    let code = generate_invite_code();
+    let code_prefix: String = code.chars().take(INVITE_PREFIX.len() + 6).collect();
+
+    match sqlx::query_as::<_, Invite>(
+        "INSERT INTO invites (code_hash, code_prefix, note, max_uses, created_by, expires_at)
+         VALUES (?, ?, ?, ?, ?, datetime('now', ?))
+         RETURNING id, code_prefix, note, max_uses, use_count, created_by, created_at,
+                   expires_at, revoked_at",
+    )
+    .bind(hash_token(&code))
+    .bind(&code_prefix)
+    .bind(form.note.trim())
+    .bind(form.max_uses)
+    .bind(admin.id)
+    .bind(format!("+{} days", days))
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(invite) => {
+            log::info!(
+                "User '{}' created invite {} ({} uses, {} days).",
+                admin.username,
+                invite.id,
+                invite.max_uses,
+                days
+            );
+            HttpResponse::Created().json(serde_json::json!({
+                "code": code,
+                "invite": invite
+            }))
+        }
+        Err(e) => {
+            log::error!("Failed to create invite: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error creating invite"}))
+        }
+    }
+}
+
+#[get("/api/admin/invites", wrap = "RequirePermission::new(USERS_MANAGE)")]
+async fn list_invites(pool: web::Data<SqlitePool>) -> impl Responder {
+    match sqlx::query_as::<_, Invite>(
+        "SELECT id, code_prefix, note, max_uses, use_count, created_by, created_at,
+                expires_at, revoked_at
+         FROM invites ORDER BY created_at DESC",
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(invites) => HttpResponse::Ok().json(serde_json::json!({ "invites": invites })),
+        Err(e) => {
+            log::error!("Failed to list invites: {:?}", e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error listing invites"}))
+        }
+    }
+}
+
+#[delete(
+    "/api/admin/invites/{id}",
+    wrap = "RequirePermission::new(USERS_MANAGE)"
+)]
+async fn revoke_invite(
+    pool: web::Data<SqlitePool>,
+    admin: AuthenticatedUser,
+    path: web::Path<i64>,
+) -> impl Responder {
+    let invite_id = path.into_inner();
+    match sqlx::query(
+        "UPDATE invites SET revoked_at = CURRENT_TIMESTAMP WHERE id = ? AND revoked_at IS NULL",
+    )
+    .bind(invite_id)
+    .execute(pool.get_ref())
+    .await
+    {
+        Ok(result) if result.rows_affected() == 1 => {
+            log::info!("User '{}' revoked invite {}.", admin.username, invite_id);
+            HttpResponse::Ok().json(serde_json::json!({"message": "Invite revoked"}))
+        }
+        Ok(_) => HttpResponse::NotFound().json(serde_json::json!({"error": "Invite not found"})),
+        Err(e) => {
+            log::error!("Failed to revoke invite {}: {:?}", invite_id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error revoking invite"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(registration_info)
+        .service(create_invite)
+        .service(list_invites)
+        .service(revoke_invite);
+}
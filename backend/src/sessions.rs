@@ -0,0 +1,377 @@
+// backend/src/sessions.rs
+// --- Server-Side Sessions ---
+// Session state lives in the `sessions` table; the cookie only carries a random key.
+// Because the server holds the state, sessions can be listed and revoked, and a
+// stolen cookie stops working as soon as its session is deleted.
+
+use std::collections::HashMap;
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_session::{Session, SessionInsertError};
+use actix_web::cookie::time::Duration;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chrono::NaiveDateTime;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sqlx::{FromRow, SqlitePool};
+
+use crate::audit::{self, AuthEvent, ClientInfo};
+use crate::auth::AuthenticatedUser;
+use crate::csrf::CSRF_STATE_KEY;
+use crate::rbac::{RequirePermission, ACCOUNT_MANAGE};
+use crate::tokens::hash_token;
+
+const SESSION_KEY_LEN: usize = 64;
+const SESSION_ID_LEN: usize = 16;
+
+/// Session state entry holding the public identifier of the session row.
+pub const SESSION_ID_STATE_KEY: &str = "session_id";
+/// Session state entries recorded by `start_login` so sessions can be told apart.
+pub const IP_STATE_KEY: &str = "ip";
+pub const USER_AGENT_STATE_KEY: &str = "user_agent";
+
+#[derive(FromRow, Debug)]
+struct SessionRow {
+    id: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    created_at: NaiveDateTime,
+    last_seen_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+}
+
+pub fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// Reads a JSON-encoded value from raw session state, as stored by `Session::insert`.
+fn state_value<T: serde::de::DeserializeOwned>(
+    state: &HashMap<String, String>,
+    key: &str,
+) -> Option<T> {
+    state
+        .get(key)
+        .and_then(|raw| serde_json::from_str(raw).ok())
+}
+
+/// Turns `session` into a logged in session for `user_id`. Every login path goes
+/// through here so they all produce the same session.
+pub fn start_login(
+    session: &Session,
+    client: &ClientInfo,
+    user_id: i64,
+) -> Result<(), SessionInsertError> {
+    // New session key on login, so a key planted before login is useless.
+    session.renew();
+    // The pre-login CSRF token may have been seen by whoever planted the session.
+    session.remove(CSRF_STATE_KEY);
+    session.insert(IP_STATE_KEY, &client.ip)?;
+    session.insert(USER_AGENT_STATE_KEY, &client.user_agent)?;
+    session.insert("user_id", user_id)
+}
+
+// --- Session Store ---
+
+/// `SessionStore` backed by SQLite, with an idle expiry (the TTL handed in by
+/// `SessionMiddleware`) and an absolute expiry counted from the first save.
+#[derive(Clone)]
+pub struct SqliteSessionStore {
+    pool: SqlitePool,
+    absolute_ttl: Duration,
+}
+
+impl SqliteSessionStore {
+    pub fn new(pool: SqlitePool, absolute_ttl: Duration) -> Self {
+        SqliteSessionStore { pool, absolute_ttl }
+    }
+
+    async fn delete_expired(&self) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "DELETE FROM sessions
+             WHERE expires_at <= CURRENT_TIMESTAMP OR absolute_expires_at <= CURRENT_TIMESTAMP",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let state = sqlx::query_scalar::<_, String>(
+            "SELECT state FROM sessions
+             WHERE key_hash = ?
+               AND expires_at > CURRENT_TIMESTAMP
+               AND absolute_expires_at > CURRENT_TIMESTAMP",
+        )
+        .bind(hash_token(session_key.as_ref()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| LoadError::Other(e.into()))?;
+
+        state
+            .map(|state| serde_json::from_str(&state))
+            .transpose()
+            .map_err(|e| LoadError::Deserialization(e.into()))
+    }
+
+    async fn save(
+        &self,
+        mut session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        if let Err(e) = self.delete_expired().await {
+            log::warn!("Failed to delete expired sessions: {:?}", e);
+        }
+
+        let session_key = random_string(SESSION_KEY_LEN);
+        let session_id = random_string(SESSION_ID_LEN);
+        session_state.insert(
+            SESSION_ID_STATE_KEY.to_string(),
+            serde_json::to_string(&session_id).map_err(|e| SaveError::Serialization(e.into()))?,
+        );
+        let state = serde_json::to_string(&session_state)
+            .map_err(|e| SaveError::Serialization(e.into()))?;
+
+        sqlx::query(
+            "INSERT INTO sessions
+                (id, key_hash, user_id, state, ip, user_agent, expires_at, absolute_expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, datetime('now', ?), datetime('now', ?))",
+        )
+        .bind(&session_id)
+        .bind(hash_token(&session_key))
+        .bind(state_value::<i64>(&session_state, "user_id"))
+        .bind(&state)
+        .bind(state_value::<String>(&session_state, IP_STATE_KEY))
+        .bind(state_value::<String>(&session_state, USER_AGENT_STATE_KEY))
+        .bind(format!("+{} seconds", ttl.whole_seconds()))
+        .bind(format!("+{} seconds", self.absolute_ttl.whole_seconds()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SaveError::Other(e.into()))?;
+
+        SessionKey::try_from(session_key).map_err(|e| SaveError::Other(e.into()))
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        mut session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state = serde_json::to_string(&session_state)
+            .map_err(|e| UpdateError::Serialization(e.into()))?;
+
+        let result = sqlx::query(
+            "UPDATE sessions
+             SET state = ?, user_id = ?, ip = ?, user_agent = ?,
+                 last_seen_at = CURRENT_TIMESTAMP, expires_at = datetime('now', ?)
+             WHERE key_hash = ?
+               AND expires_at > CURRENT_TIMESTAMP
+               AND absolute_expires_at > CURRENT_TIMESTAMP",
+        )
+        .bind(&state)
+        .bind(state_value::<i64>(&session_state, "user_id"))
+        .bind(state_value::<String>(&session_state, IP_STATE_KEY))
+        .bind(state_value::<String>(&session_state, USER_AGENT_STATE_KEY))
+        .bind(format!("+{} seconds", ttl.whole_seconds()))
+        .bind(hash_token(session_key.as_ref()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UpdateError::Other(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            // The session expired or was revoked while this request ran. Start a fresh,
+            // anonymous one rather than bringing the revoked login back to life.
+            session_state.remove("user_id");
+            return self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(err) => UpdateError::Serialization(err),
+                SaveError::Other(err) => UpdateError::Other(err),
+            });
+        }
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(
+        &self,
+        session_key: &SessionKey,
+        ttl: &Duration,
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "UPDATE sessions
+             SET last_seen_at = CURRENT_TIMESTAMP, expires_at = datetime('now', ?)
+             WHERE key_hash = ?",
+        )
+        .bind(format!("+{} seconds", ttl.whole_seconds()))
+        .bind(hash_token(session_key.as_ref()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> Result<(), anyhow::Error> {
+        sqlx::query("DELETE FROM sessions WHERE key_hash = ?")
+            .bind(hash_token(session_key.as_ref()))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+// --- Handlers ---
+
+#[get("/api/sessions", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn list_sessions(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    session: Session,
+) -> impl Responder {
+    let current = session.get::<String>(SESSION_ID_STATE_KEY).ok().flatten();
+
+    match sqlx::query_as::<_, SessionRow>(
+        "SELECT id, ip, user_agent, created_at, last_seen_at, expires_at FROM sessions
+         WHERE user_id = ?
+           AND expires_at > CURRENT_TIMESTAMP
+           AND absolute_expires_at > CURRENT_TIMESTAMP
+         ORDER BY last_seen_at DESC",
+    )
+    .bind(user.id)
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(rows) => {
+            let sessions: Vec<_> = rows
+                .into_iter()
+                .map(|row| {
+                    let is_current = current.as_deref() == Some(row.id.as_str());
+                    serde_json::json!({
+                        "id": row.id,
+                        "ip": row.ip,
+                        "user_agent": row.user_agent,
+                        "created_at": row.created_at,
+                        "last_seen_at": row.last_seen_at,
+                        "expires_at": row.expires_at,
+                        "current": is_current
+                    })
+                })
+                .collect();
+            HttpResponse::Ok().json(serde_json::json!({ "sessions": sessions }))
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to list sessions for user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error listing sessions"}))
+        }
+    }
+}
+
+#[delete("/api/sessions/{id}", wrap = "RequirePermission::new(ACCOUNT_MANAGE)")]
+async fn revoke_session(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    session: Session,
+    client: ClientInfo,
+    path: web::Path<String>,
+) -> impl Responder {
+    let session_id = path.into_inner();
+
+    match sqlx::query("DELETE FROM sessions WHERE id = ? AND user_id = ?")
+        .bind(&session_id)
+        .bind(user.id)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected() == 1 => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::SessionRevoked,
+                Some(user.id),
+                Some(&user.username),
+                Some(&format!("session {}", session_id)),
+            )
+            .await;
+            if session.get::<String>(SESSION_ID_STATE_KEY).ok().flatten() == Some(session_id) {
+                session.purge();
+            }
+            log::info!("User '{}' revoked a session.", user.username);
+            HttpResponse::Ok().json(serde_json::json!({"message": "Session revoked"}))
+        }
+        Ok(_) => HttpResponse::NotFound().json(serde_json::json!({"error": "Session not found"})),
+        Err(e) => {
+            log::error!(
+                "Failed to revoke session for user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error revoking session"}))
+        }
+    }
+}
+
+#[post(
+    "/api/sessions/revoke-all",
+    wrap = "RequirePermission::new(ACCOUNT_MANAGE)"
+)]
+async fn revoke_all_sessions(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    session: Session,
+    client: ClientInfo,
+) -> impl Responder {
+    match sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+        .bind(user.id)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(result) => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::AllSessionsRevoked,
+                Some(user.id),
+                Some(&user.username),
+                Some(&format!("{} sessions", result.rows_affected())),
+            )
+            .await;
+            session.purge();
+            log::info!(
+                "User '{}' logged out everywhere ({} sessions revoked).",
+                user.username,
+                result.rows_affected()
+            );
+            HttpResponse::Ok().json(serde_json::json!({
+                "message": "Logged out everywhere",
+                "revoked": result.rows_affected()
+            }))
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to revoke sessions for user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error revoking sessions"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_sessions)
+        .service(revoke_session)
+        .service(revoke_all_sessions);
+}
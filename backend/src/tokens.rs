@@ -0,0 +1,263 @@
+// backend/src/tokens.rs
+// --- Personal API Tokens ---
+// Users create named, scoped tokens for scripts and integrations. Only the SHA-256
+// hash of a token is stored; the plaintext is shown once, in the create response.
+
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, SqlitePool};
+
+use crate::audit::{self, AuthEvent, ClientInfo};
+use crate::auth::{AuthMethod, AuthenticatedUser};
+use crate::rbac::{self, RequirePermission, TOKENS_MANAGE};
+
+const TOKEN_PREFIX: &str = "wk_";
+const TOKEN_BYTES: usize = 32;
+const MAX_TOKEN_NAME_LEN: usize = 64;
+
+#[derive(Deserialize, Debug)]
+struct CreateTokenForm {
+    name: String,
+    scopes: Vec<String>,
+    expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize, FromRow, Debug)]
+struct ApiToken {
+    id: i64,
+    name: String,
+    token_prefix: String,
+    scopes: String,
+    created_at: NaiveDateTime,
+    last_used_at: Option<NaiveDateTime>,
+    expires_at: Option<NaiveDateTime>,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(FromRow)]
+struct TokenOwnerRow {
+    token_id: i64,
+    user_id: i64,
+    username: String,
+    role: String,
+    scopes: String,
+}
+
+// --- Helper Functions ---
+fn generate_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, hex::encode(bytes))
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes.split_whitespace().map(str::to_string).collect()
+}
+
+/// Looks up an unrevoked, unexpired token and returns its owner, with permissions
+/// limited to those both the owner's role and the token's scopes allow.
+pub async fn authenticate(
+    pool: &SqlitePool,
+    token: &str,
+) -> Result<Option<AuthenticatedUser>, sqlx::Error> {
+    let row = sqlx::query_as::<_, TokenOwnerRow>(
+        "SELECT t.id AS token_id, u.id AS user_id, u.username, u.role, t.scopes
+         FROM api_tokens t JOIN users u ON u.id = t.user_id
+         WHERE t.token_hash = ?
+           AND t.revoked_at IS NULL
+           AND (t.expires_at IS NULL OR t.expires_at > CURRENT_TIMESTAMP)",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    if let Err(e) =
+        sqlx::query("UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(row.token_id)
+            .execute(pool)
+            .await
+    {
+        // Not fatal: the token is valid, we just couldn't record its use.
+        log::warn!(
+            "Failed to update last_used_at for token {}: {:?}",
+            row.token_id,
+            e
+        );
+    }
+
+    let scopes = split_scopes(&row.scopes);
+    let permissions = rbac::role_permissions(pool, &row.role)
+        .await?
+        .into_iter()
+        .filter(|p| scopes.contains(p))
+        .collect();
+
+    Ok(Some(AuthenticatedUser {
+        id: row.user_id,
+        username: row.username,
+        role: row.role,
+        method: AuthMethod::Token,
+        permissions,
+    }))
+}
+
+// --- Handlers ---
+
+#[post("/api/tokens", wrap = "RequirePermission::new(TOKENS_MANAGE)")]
+async fn create_token(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+    payload: web::Json<CreateTokenForm>,
+) -> impl Responder {
+    let form = payload.into_inner();
+    let name = form.name.trim();
+    if name.is_empty() || name.len() > MAX_TOKEN_NAME_LEN {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": "Token name must be between 1 and 64 characters"}));
+    }
+    if let Some(unknown) = form
+        .scopes
+        .iter()
+        .find(|s| !rbac::ALL_PERMISSIONS.contains(&s.as_str()))
+    {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({"error": format!("Unknown scope '{}'", unknown)}));
+    }
+    // A token may never grant more than its creator holds.
+    if let Some(missing) = form.scopes.iter().find(|s| !user.has_permission(s)) {
+        return HttpResponse::Forbidden()
+            .json(serde_json::json!({"error": format!("Cannot grant scope '{}'", missing)}));
+    }
+
+    let mut scopes = form.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    let scopes = scopes.join(" ");
+
+    let token = generate_token();
+    let token_prefix: String = token.chars().take(TOKEN_PREFIX.len() + 8).collect();
+    let expires_modifier = form.expires_in_days.map(|days| format!("+{} days", days));
+
+    match sqlx::query_as::<_, ApiToken>(
+        "INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, expires_at)
+         VALUES (?, ?, ?, ?, ?, CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', ?) END)
+         RETURNING id, name, token_prefix, scopes, created_at, last_used_at, expires_at, revoked_at",
+    )
+    .bind(user.id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&token_prefix)
+    .bind(&scopes)
+    .bind(&expires_modifier)
+    .bind(&expires_modifier)
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(created) => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::TokenCreated,
+                Some(user.id),
+                Some(&user.username),
+                Some(&format!("token {} ({})", created.id, created.name)),
+            )
+            .await;
+            log::info!(
+                "User '{}' created API token '{}' (authenticated via {:?}).",
+                user.username,
+                created.name,
+                user.method
+            );
+            HttpResponse::Created().json(serde_json::json!({
+                "token": token,
+                "details": created
+            }))
+        }
+        Err(e) => {
+            log::error!("Failed to create API token for user '{}': {:?}", user.username, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error creating token"}))
+        }
+    }
+}
+
+#[get("/api/tokens", wrap = "RequirePermission::new(TOKENS_MANAGE)")]
+async fn list_tokens(pool: web::Data<SqlitePool>, user: AuthenticatedUser) -> impl Responder {
+    match sqlx::query_as::<_, ApiToken>(
+        "SELECT id, name, token_prefix, scopes, created_at, last_used_at, expires_at, revoked_at
+         FROM api_tokens WHERE user_id = ? ORDER BY created_at DESC",
+    )
+    .bind(user.id)
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(tokens) => HttpResponse::Ok().json(serde_json::json!({ "tokens": tokens })),
+        Err(e) => {
+            log::error!(
+                "Failed to list API tokens for user '{}': {:?}",
+                user.username,
+                e
+            );
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error listing tokens"}))
+        }
+    }
+}
+
+#[delete("/api/tokens/{id}", wrap = "RequirePermission::new(TOKENS_MANAGE)")]
+async fn revoke_token(
+    pool: web::Data<SqlitePool>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<i64>,
+) -> impl Responder {
+    let token_id = path.into_inner();
+    match sqlx::query(
+        "UPDATE api_tokens SET revoked_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND user_id = ? AND revoked_at IS NULL",
+    )
+    .bind(token_id)
+    .bind(user.id)
+    .execute(pool.get_ref())
+    .await
+    {
+        Ok(result) if result.rows_affected() == 1 => {
+            audit::record(
+                pool.get_ref(),
+                &client,
+                AuthEvent::TokenRevoked,
+                Some(user.id),
+                Some(&user.username),
+                Some(&format!("token {}", token_id)),
+            )
+            .await;
+            log::info!("User '{}' revoked API token {}.", user.username, token_id);
+            HttpResponse::Ok().json(serde_json::json!({"message": "Token revoked"}))
+        }
+        Ok(_) => HttpResponse::NotFound().json(serde_json::json!({"error": "Token not found"})),
+        Err(e) => {
+            log::error!("Failed to revoke API token {}: {:?}", token_id, e);
+            HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "Database error revoking token"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_token)
+        .service(list_tokens)
+        .service(revoke_token);
+}
@@ -0,0 +1,620 @@
+// backend/src/weather.rs
+// --- Weather ---
+// Current conditions and a daily forecast for a city or a coordinate pair. Cities are
+// resolved through the local geocoding table first (see `geocoding.rs`). Data comes
+// from a `WeatherProvider`: Open-Meteo over HTTP, or a local mock for development and
+// offline use. Answers are cached per location for the configured TTL.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use actix_web::{get, web, HttpResponse, Responder};
+use awc::Client;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use whoknows_config::{WeatherProviderKind, WeatherSettings};
+
+use crate::geocoding;
+
+const DEFAULT_FORECAST_DAYS: u8 = 3;
+const MAX_FORECAST_DAYS: u8 = 7;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+// Entries per cache; past it the oldest go first, so odd queries can't grow memory.
+const CACHE_CAPACITY: usize = 10_000;
+
+// --- Data Types ---
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Location {
+    name: String,
+    country: Option<String>,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Conditions {
+    time: String,
+    temperature_c: f64,
+    apparent_temperature_c: Option<f64>,
+    humidity_percent: Option<f64>,
+    wind_speed_kmh: Option<f64>,
+    wind_direction_deg: Option<f64>,
+    weather_code: u8,
+    description: &'static str,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DailyForecast {
+    date: String,
+    temperature_min_c: Option<f64>,
+    temperature_max_c: Option<f64>,
+    precipitation_mm: Option<f64>,
+    precipitation_probability_percent: Option<f64>,
+    weather_code: Option<u8>,
+    description: Option<&'static str>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Forecast {
+    current: Conditions,
+    daily: Vec<DailyForecast>,
+}
+
+/// Text for a WMO weather interpretation code, as used by Open-Meteo.
+fn describe(code: u8) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 | 63 | 65 => "Rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 | 77 => "Snow",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+// --- Providers ---
+
+/// A source of weather data. Providers are shared by all workers, but their futures
+/// run on the worker that awaits them and so needn't be `Send` (awc's aren't).
+pub trait WeatherProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Looks up a city by name; `None` when the provider doesn't know it.
+    fn geocode<'a>(&'a self, city: &'a str)
+        -> LocalBoxFuture<'a, Result<Option<Location>, String>>;
+
+    /// Current conditions and `days` days of daily forecast, starting today.
+    fn forecast<'a>(
+        &'a self,
+        location: &'a Location,
+        days: u8,
+    ) -> LocalBoxFuture<'a, Result<Forecast, String>>;
+}
+
+/// Open-Meteo's free forecast and geocoding APIs; no API key needed.
+struct OpenMeteoProvider {
+    forecast_url: String,
+    geocoding_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodingResponse {
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodingResult {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    country: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoForecast {
+    current: OpenMeteoCurrent,
+    daily: OpenMeteoDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoCurrent {
+    time: String,
+    temperature_2m: f64,
+    relative_humidity_2m: Option<f64>,
+    apparent_temperature: Option<f64>,
+    weather_code: u8,
+    wind_speed_10m: Option<f64>,
+    wind_direction_10m: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoDaily {
+    time: Vec<String>,
+    #[serde(default)]
+    weather_code: Vec<Option<u8>>,
+    #[serde(default)]
+    temperature_2m_max: Vec<Option<f64>>,
+    #[serde(default)]
+    temperature_2m_min: Vec<Option<f64>>,
+    #[serde(default)]
+    precipitation_sum: Vec<Option<f64>>,
+    #[serde(default)]
+    precipitation_probability_max: Vec<Option<f64>>,
+}
+
+fn http_client() -> Client {
+    Client::builder().timeout(HTTP_TIMEOUT).finish()
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    base_url: &str,
+    params: &[(&str, String)],
+) -> Result<T, String> {
+    let query = serde_urlencoded::to_string(params)
+        .map_err(|e| format!("Failed to encode query for {}: {}", base_url, e))?;
+    let url = format!("{}?{}", base_url, query);
+    let mut response = http_client()
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Request to {} failed: {}", base_url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Request to {} returned {}",
+            base_url,
+            response.status()
+        ));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid response from {}: {}", base_url, e))
+}
+
+impl WeatherProvider for OpenMeteoProvider {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+
+    fn geocode<'a>(
+        &'a self,
+        city: &'a str,
+    ) -> LocalBoxFuture<'a, Result<Option<Location>, String>> {
+        Box::pin(async move {
+            let response: GeocodingResponse = get_json(
+                &self.geocoding_url,
+                &[
+                    ("name", city.to_string()),
+                    ("count", "1".to_string()),
+                    ("language", "en".to_string()),
+                    ("format", "json".to_string()),
+                ],
+            )
+            .await?;
+            Ok(response.results.into_iter().next().map(|r| Location {
+                name: r.name,
+                country: r.country,
+                latitude: r.latitude,
+                longitude: r.longitude,
+            }))
+        })
+    }
+
+    fn forecast<'a>(
+        &'a self,
+        location: &'a Location,
+        days: u8,
+    ) -> LocalBoxFuture<'a, Result<Forecast, String>> {
+        Box::pin(async move {
+            let response: OpenMeteoForecast = get_json(
+                &self.forecast_url,
+                &[
+                    ("latitude", location.latitude.to_string()),
+                    ("longitude", location.longitude.to_string()),
+                    (
+                        "current",
+                        "temperature_2m,relative_humidity_2m,apparent_temperature,\
+                         weather_code,wind_speed_10m,wind_direction_10m"
+                            .to_string(),
+                    ),
+                    (
+                        "daily",
+                        "weather_code,temperature_2m_max,temperature_2m_min,\
+                         precipitation_sum,precipitation_probability_max"
+                            .to_string(),
+                    ),
+                    ("timezone", "auto".to_string()),
+                    ("forecast_days", days.to_string()),
+                ],
+            )
+            .await?;
+
+            let current = response.current;
+            let daily = response.daily;
+            let at = |values: &[Option<f64>], i: usize| values.get(i).copied().flatten();
+            Ok(Forecast {
+                current: Conditions {
+                    time: current.time,
+                    temperature_c: current.temperature_2m,
+                    apparent_temperature_c: current.apparent_temperature,
+                    humidity_percent: current.relative_humidity_2m,
+                    wind_speed_kmh: current.wind_speed_10m,
+                    wind_direction_deg: current.wind_direction_10m,
+                    weather_code: current.weather_code,
+                    description: describe(current.weather_code),
+                },
+                daily: daily
+                    .time
+                    .iter()
+                    .enumerate()
+                    .map(|(i, date)| {
+                        let code = daily.weather_code.get(i).copied().flatten();
+                        DailyForecast {
+                            date: date.clone(),
+                            temperature_min_c: at(&daily.temperature_2m_min, i),
+                            temperature_max_c: at(&daily.temperature_2m_max, i),
+                            precipitation_mm: at(&daily.precipitation_sum, i),
+                            precipitation_probability_percent: at(
+                                &daily.precipitation_probability_max,
+                                i,
+                            ),
+                            weather_code: code,
+                            description: code.map(describe),
+                        }
+                    })
+                    .collect(),
+            })
+        })
+    }
+}
+
+/// Made-up but stable weather for a handful of cities, for development and tests
+/// without network access.
+struct MockProvider;
+
+const MOCK_CITIES: &[(&str, &str, f64, f64)] = &[
+    ("Copenhagen", "Denmark", 55.6761, 12.5683),
+    ("Aarhus", "Denmark", 56.1567, 10.2108),
+    ("Odense", "Denmark", 55.3959, 10.3883),
+    ("Aalborg", "Denmark", 57.0488, 9.9217),
+    ("London", "United Kingdom", 51.5085, -0.1257),
+    ("Berlin", "Germany", 52.5244, 13.4105),
+];
+const MOCK_CODES: &[u8] = &[0, 2, 3, 61, 80, 1, 45];
+
+impl WeatherProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn geocode<'a>(
+        &'a self,
+        city: &'a str,
+    ) -> LocalBoxFuture<'a, Result<Option<Location>, String>> {
+        let location = MOCK_CITIES
+            .iter()
+            .find(|(name, ..)| name.eq_ignore_ascii_case(city.trim()))
+            .map(|&(name, country, latitude, longitude)| Location {
+                name: name.to_string(),
+                country: Some(country.to_string()),
+                latitude,
+                longitude,
+            });
+        Box::pin(async move { Ok(location) })
+    }
+
+    fn forecast<'a>(
+        &'a self,
+        location: &'a Location,
+        days: u8,
+    ) -> LocalBoxFuture<'a, Result<Forecast, String>> {
+        // Colder further from the equator, varied a little by longitude.
+        let base = (30.0 - location.latitude.abs() * 0.4).round();
+        let seed = (location.longitude.abs() * 10.0) as usize;
+        let now = chrono::Utc::now();
+        let code = MOCK_CODES[seed % MOCK_CODES.len()];
+
+        let forecast = Forecast {
+            current: Conditions {
+                time: now.format("%Y-%m-%dT%H:00").to_string(),
+                temperature_c: base,
+                apparent_temperature_c: Some(base - 2.0),
+                humidity_percent: Some(70.0),
+                wind_speed_kmh: Some(12.0),
+                wind_direction_deg: Some(((seed * 37) % 360) as f64),
+                weather_code: code,
+                description: describe(code),
+            },
+            daily: (0..days)
+                .map(|day| {
+                    let code = MOCK_CODES[(seed + day as usize) % MOCK_CODES.len()];
+                    let wet = code >= 51;
+                    DailyForecast {
+                        date: (now.date_naive() + chrono::Days::new(day.into())).to_string(),
+                        temperature_min_c: Some(base - 4.0 + f64::from(day % 3)),
+                        temperature_max_c: Some(base + 3.0 + f64::from(day % 3)),
+                        precipitation_mm: Some(if wet { 4.2 } else { 0.0 }),
+                        precipitation_probability_percent: Some(if wet { 80.0 } else { 10.0 }),
+                        weather_code: Some(code),
+                        description: Some(describe(code)),
+                    }
+                })
+                .collect(),
+        };
+        Box::pin(async move { Ok(forecast) })
+    }
+}
+
+// --- Cache ---
+
+/// Map whose entries disappear `ttl` after they were inserted, holding at most
+/// `capacity` of them. Entries leave in insertion order, when they expire or to make
+/// room, so each insert only touches the ones that go.
+struct TtlCache<V> {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<Entries<V>>,
+}
+
+struct Entries<V> {
+    values: HashMap<String, (Instant, V)>,
+    // Keys oldest first. A key inserted again also has an older, stale element here,
+    // recognised by its insertion time.
+    order: VecDeque<(Instant, String)>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        TtlCache {
+            ttl,
+            capacity,
+            entries: Mutex::new(Entries {
+                values: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<V> {
+        let entries = self.entries.lock().expect("lock poisoned");
+        entries
+            .values
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn insert(&self, key: String, value: V) {
+        if self.ttl.is_zero() || self.capacity == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        let entries = &mut *entries;
+        while let Some((inserted, _)) = entries.order.front() {
+            if now.duration_since(*inserted) < self.ttl && entries.values.len() < self.capacity {
+                break;
+            }
+            let (inserted, oldest) = entries.order.pop_front().expect("front was checked");
+            if entries
+                .values
+                .get(&oldest)
+                .is_some_and(|(at, _)| *at == inserted)
+            {
+                entries.values.remove(&oldest);
+            }
+        }
+        entries.values.insert(key.clone(), (now, value));
+        entries.order.push_back((now, key));
+    }
+}
+
+// --- Service ---
+
+struct WeatherService {
+    provider: Box<dyn WeatherProvider>,
+    default_city: String,
+    locations: TtlCache<Location>,
+    forecasts: TtlCache<Forecast>,
+}
+
+static SERVICE: OnceLock<WeatherService> = OnceLock::new();
+
+fn build_service(
+    provider: Box<dyn WeatherProvider>,
+    default_city: String,
+    ttl: Duration,
+) -> WeatherService {
+    WeatherService {
+        provider,
+        default_city,
+        locations: TtlCache::new(ttl, CACHE_CAPACITY),
+        forecasts: TtlCache::new(ttl, CACHE_CAPACITY),
+    }
+}
+
+/// Sets up the configured provider (`open-meteo` by default, or `mock`) and cache.
+pub fn init(settings: &WeatherSettings) -> Result<(), String> {
+    let provider: Box<dyn WeatherProvider> = match settings.provider {
+        WeatherProviderKind::OpenMeteo => Box::new(OpenMeteoProvider {
+            forecast_url: settings.open_meteo_forecast_url.clone(),
+            geocoding_url: settings.open_meteo_geocoding_url.clone(),
+        }),
+        WeatherProviderKind::Mock => Box::new(MockProvider),
+    };
+
+    log::info!(
+        "Weather provider: {}, cache TTL {}s, default city {}.",
+        provider.name(),
+        settings.cache_ttl_secs,
+        settings.default_city
+    );
+    SERVICE
+        .set(build_service(
+            provider,
+            settings.default_city.clone(),
+            Duration::from_secs(settings.cache_ttl_secs),
+        ))
+        .map_err(|_| "Weather service already initialised".to_string())
+}
+
+fn service() -> &'static WeatherService {
+    SERVICE.get_or_init(|| {
+        let defaults = WeatherSettings::default();
+        build_service(
+            Box::new(MockProvider),
+            defaults.default_city,
+            Duration::from_secs(defaults.cache_ttl_secs),
+        )
+    })
+}
+
+impl WeatherService {
+    /// Resolves a city name through the local `cities` table, and through the
+    /// provider's own geocoding for cities the table doesn't have.
+    async fn locate(&self, pool: &SqlitePool, city: &str) -> Result<Option<Location>, String> {
+        let key = geocoding::fold(city);
+        if let Some(location) = self.locations.get(&key) {
+            return Ok(Some(location));
+        }
+        let local = geocoding::lookup(pool, city).await.unwrap_or_else(|e| {
+            log::error!("Failed to look up city '{}' locally: {:?}", city, e);
+            None
+        });
+        let location = match local {
+            Some(found) => Some(Location {
+                name: found.name,
+                country: Some(found.country_code),
+                latitude: found.latitude,
+                longitude: found.longitude,
+            }),
+            None => self.provider.geocode(city.trim()).await?,
+        };
+        if let Some(location) = &location {
+            self.locations.insert(key, location.clone());
+        }
+        Ok(location)
+    }
+
+    /// The forecast for `location`, and whether it came from the cache. Locations are
+    /// keyed to about a kilometre, so nearby coordinates share an entry.
+    async fn forecast(&self, location: &Location, days: u8) -> Result<(Forecast, bool), String> {
+        let key = format!(
+            "{},{},{}",
+            (location.latitude * 100.0).round() as i32,
+            (location.longitude * 100.0).round() as i32,
+            days
+        );
+        if let Some(forecast) = self.forecasts.get(&key) {
+            return Ok((forecast, true));
+        }
+        let forecast = self.provider.forecast(location, days).await?;
+        self.forecasts.insert(key, forecast.clone());
+        Ok((forecast, false))
+    }
+}
+
+// --- Handlers ---
+
+/// Rounds to 0.01°, about a kilometre.
+fn round_coordinate(degrees: f64) -> f64 {
+    (degrees * 100.0).round() / 100.0
+}
+
+#[derive(Deserialize, Debug)]
+struct WeatherQuery {
+    city: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    days: Option<u8>,
+}
+
+#[get("/api/weather")]
+async fn get_weather(
+    pool: web::Data<SqlitePool>,
+    query: web::Query<WeatherQuery>,
+) -> impl Responder {
+    let service = service();
+    let query = query.into_inner();
+
+    let days = query.days.unwrap_or(DEFAULT_FORECAST_DAYS);
+    if !(1..=MAX_FORECAST_DAYS).contains(&days) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("days must be between 1 and {}", MAX_FORECAST_DAYS)
+        }));
+    }
+
+    let location = match (query.lat, query.lon) {
+        (Some(latitude), Some(longitude)) => {
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return HttpResponse::BadRequest()
+                    .json(serde_json::json!({"error": "Coordinates out of range"}));
+            }
+            // Rounded like the cache key, so the cached forecast is for these coordinates
+            let (latitude, longitude) = (round_coordinate(latitude), round_coordinate(longitude));
+            Location {
+                name: format!("{:.2}, {:.2}", latitude, longitude),
+                country: None,
+                latitude,
+                longitude,
+            }
+        }
+        (None, None) => {
+            let city = query
+                .city
+                .as_deref()
+                .map(str::trim)
+                .filter(|city| !city.is_empty())
+                .unwrap_or(&service.default_city);
+            match service.locate(pool.get_ref(), city).await {
+                Ok(Some(location)) => location,
+                Ok(None) => {
+                    return HttpResponse::NotFound()
+                        .json(serde_json::json!({"error": format!("Unknown city '{}'", city)}));
+                }
+                Err(e) => {
+                    log::error!("Failed to look up city '{}': {}", city, e);
+                    return HttpResponse::BadGateway()
+                        .json(serde_json::json!({"error": "Weather service unavailable"}));
+                }
+            }
+        }
+        _ => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({"error": "lat and lon must be given together"}));
+        }
+    };
+
+    match service.forecast(&location, days).await {
+        Ok((forecast, cached)) => HttpResponse::Ok().json(serde_json::json!({
+            "data": {
+                "location": location,
+                "current": forecast.current,
+                "forecast": forecast.daily,
+                "provider": service.provider.name(),
+                "cached": cached
+            }
+        })),
+        Err(e) => {
+            log::error!("Failed to fetch weather for '{}': {}", location.name, e);
+            HttpResponse::BadGateway()
+                .json(serde_json::json!({"error": "Weather service unavailable"}))
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_weather);
+}
@@ -0,0 +1,739 @@
+// config/src/backend.rs
+// --- Backend Settings ---
+// Everything the backend reads at startup, grouped the way the TOML file is:
+// `[backend]`, `[backend.session]`, `[backend.argon2]`, `[backend.password]`,
+// `[backend.registration]`, `[backend.oidc]`, `[backend.pow]`, `[backend.weather]`,
+// `[backend.mail]` and `[backend.health]`.
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::check_url;
+use crate::env_layer::EnvLayer;
+use crate::redact;
+use crate::Environment;
+
+const BACKEND_INTERNAL_PORT_KEY: &str = "BACKEND_INTERNAL_PORT";
+const DATABASE_URL_KEY: &str = "DATABASE_URL";
+const MIGRATE_ON_STARTUP_KEY: &str = "MIGRATE_ON_STARTUP";
+const BUILD_VERSION_KEY: &str = "BUILD_VERSION";
+const PUBLIC_BASE_URL_KEY: &str = "PUBLIC_BASE_URL";
+const INITIAL_ADMIN_KEY: &str = "INITIAL_ADMIN";
+const TRUSTED_PROXIES_KEY: &str = "TRUSTED_PROXIES";
+const SESSION_SECRET_KEY_KEY: &str = "SESSION_SECRET_KEY";
+const SESSION_IDLE_TIMEOUT_SECS_KEY: &str = "SESSION_IDLE_TIMEOUT_SECS";
+const SESSION_ABSOLUTE_TIMEOUT_SECS_KEY: &str = "SESSION_ABSOLUTE_TIMEOUT_SECS";
+const ARGON2_MEMORY_KIB_KEY: &str = "ARGON2_MEMORY_KIB";
+const ARGON2_ITERATIONS_KEY: &str = "ARGON2_ITERATIONS";
+const ARGON2_PARALLELISM_KEY: &str = "ARGON2_PARALLELISM";
+const PASSWORD_MIN_LENGTH_KEY: &str = "PASSWORD_MIN_LENGTH";
+const PASSWORD_MIN_STRENGTH_KEY: &str = "PASSWORD_MIN_STRENGTH";
+const BREACHED_PASSWORDS_FILE_KEY: &str = "BREACHED_PASSWORDS_FILE";
+const REGISTRATION_MODE_KEY: &str = "REGISTRATION_MODE";
+const REGISTRATION_UNIFORM_RESPONSES_KEY: &str = "REGISTRATION_UNIFORM_RESPONSES";
+const OIDC_ISSUER_URL_KEY: &str = "OIDC_ISSUER_URL";
+const OIDC_CLIENT_ID_KEY: &str = "OIDC_CLIENT_ID";
+const OIDC_CLIENT_SECRET_KEY: &str = "OIDC_CLIENT_SECRET";
+const OIDC_REDIRECT_URL_KEY: &str = "OIDC_REDIRECT_URL";
+const OIDC_AUTHORIZATION_URL_KEY: &str = "OIDC_AUTHORIZATION_URL";
+const OIDC_PROVIDER_NAME_KEY: &str = "OIDC_PROVIDER_NAME";
+const OIDC_SCOPES_KEY: &str = "OIDC_SCOPES";
+const OIDC_AUTO_REGISTER_KEY: &str = "OIDC_AUTO_REGISTER";
+const OIDC_LINK_VERIFIED_EMAIL_KEY: &str = "OIDC_LINK_VERIFIED_EMAIL";
+const OIDC_ALLOW_INSECURE_HTTP_KEY: &str = "OIDC_ALLOW_INSECURE_HTTP";
+const POW_ENABLED_KEY: &str = "POW_ENABLED";
+const POW_SECRET_KEY: &str = "POW_SECRET";
+const POW_BASE_DIFFICULTY_KEY: &str = "POW_BASE_DIFFICULTY";
+const POW_MAX_DIFFICULTY_KEY: &str = "POW_MAX_DIFFICULTY";
+const POW_LOGIN_AFTER_FAILURES_KEY: &str = "POW_LOGIN_AFTER_FAILURES";
+const WEATHER_PROVIDER_KEY: &str = "WEATHER_PROVIDER";
+const WEATHER_CACHE_TTL_SECS_KEY: &str = "WEATHER_CACHE_TTL_SECS";
+const WEATHER_DEFAULT_CITY_KEY: &str = "WEATHER_DEFAULT_CITY";
+const OPEN_METEO_FORECAST_URL_KEY: &str = "OPEN_METEO_FORECAST_URL";
+const OPEN_METEO_GEOCODING_URL_KEY: &str = "OPEN_METEO_GEOCODING_URL";
+const MAIL_TRANSPORT_KEY: &str = "MAIL_TRANSPORT";
+const MAIL_FROM_KEY: &str = "MAIL_FROM";
+const SMTP_URL_KEY: &str = "SMTP_URL";
+const HEALTH_MIN_FREE_DISK_MB_KEY: &str = "HEALTH_MIN_FREE_DISK_MB";
+
+const DEFAULT_BACKEND_PORT: u16 = 92;
+/// Where the compose setup serves the site locally; `public_base_url` in development.
+pub const DEV_PUBLIC_BASE_URL: &str = "http://localhost:8080";
+const MAX_PASSWORD_STRENGTH: u8 = 4;
+const MAX_POW_DIFFICULTY: u32 = 32;
+
+// --- Sections ---
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackendSettings {
+    pub port: u16,
+    #[serde(serialize_with = "redact::url")]
+    pub database_url: String,
+    /// Create the database and apply pending migrations at startup, instead of
+    /// refusing to start until `backend migrate` has been run.
+    pub migrate_on_startup: bool,
+    pub build_version: String,
+    /// Public address of the site, for links that leave the app (mails, redirects).
+    /// Required outside development, which falls back to `DEV_PUBLIC_BASE_URL`.
+    pub public_base_url: String,
+    /// Username given the admin role while no user has it, e.g. to set up a fresh instance.
+    pub initial_admin: Option<String>,
+    /// Addresses or networks, e.g. `172.16.0.0/12`, whose `X-Forwarded-For` names the
+    /// client; the frontend's proxy sets it. Other peers are taken at their word.
+    pub trusted_proxies: Vec<String>,
+    pub session: SessionSettings,
+    pub argon2: Argon2Settings,
+    pub password: PasswordPolicySettings,
+    pub registration: RegistrationSettings,
+    pub oidc: OidcSettings,
+    pub pow: PowSettings,
+    pub weather: WeatherSettings,
+    pub mail: MailSettings,
+    pub health: HealthSettings,
+}
+
+impl Default for BackendSettings {
+    fn default() -> Self {
+        BackendSettings {
+            port: DEFAULT_BACKEND_PORT,
+            database_url: String::new(),
+            migrate_on_startup: false,
+            build_version: "dev".to_string(),
+            public_base_url: String::new(),
+            initial_admin: None,
+            trusted_proxies: vec!["127.0.0.1".to_string(), "::1".to_string()],
+            session: SessionSettings::default(),
+            argon2: Argon2Settings::default(),
+            password: PasswordPolicySettings::default(),
+            registration: RegistrationSettings::default(),
+            oidc: OidcSettings::default(),
+            pow: PowSettings::default(),
+            weather: WeatherSettings::default(),
+            mail: MailSettings::default(),
+            health: HealthSettings::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionSettings {
+    /// 32 bytes, hex encoded, that sign and encrypt the session and flash cookies.
+    #[serde(serialize_with = "redact::secret")]
+    pub secret_key: String,
+    pub idle_timeout_secs: i64,
+    pub absolute_timeout_secs: i64,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        SessionSettings {
+            secret_key: String::new(),
+            idle_timeout_secs: 24 * 60 * 60,
+            absolute_timeout_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl SessionSettings {
+    /// The decoded secret key, or `None` unless it is exactly 64 hex digits.
+    pub fn secret_key_bytes(&self) -> Option<[u8; 32]> {
+        let digits = self.secret_key.as_bytes();
+        if digits.len() != 64 {
+            return None;
+        }
+        let mut key = [0u8; 32];
+        for (byte, pair) in key.iter_mut().zip(digits.chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).ok()?;
+            *byte = u8::from_str_radix(pair, 16).ok()?;
+        }
+        Some(key)
+    }
+}
+
+/// Argon2id costs for new password hashes; the defaults are the argon2 crate's.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Argon2Settings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Settings {
+    fn default() -> Self {
+        Argon2Settings {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicySettings {
+    pub min_length: usize,
+    /// Minimum strength score, 0-4.
+    pub min_strength: u8,
+    /// Breached-password prefix set built with `scripts/build_breached_set.py`.
+    pub breached_file: Option<PathBuf>,
+}
+
+impl Default for PasswordPolicySettings {
+    fn default() -> Self {
+        PasswordPolicySettings {
+            min_length: 8,
+            min_strength: 2,
+            breached_file: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegistrationMode {
+    #[default]
+    Open,
+    InviteOnly,
+    Closed,
+}
+
+impl RegistrationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationMode::Open => "open",
+            RegistrationMode::InviteOnly => "invite-only",
+            RegistrationMode::Closed => "closed",
+        }
+    }
+}
+
+impl FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "open" => Ok(RegistrationMode::Open),
+            "invite-only" | "invite_only" | "invite" => Ok(RegistrationMode::InviteOnly),
+            "closed" => Ok(RegistrationMode::Closed),
+            _ => Err("expected open, invite-only or closed".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistrationSettings {
+    pub mode: RegistrationMode,
+    /// Answer registrations for taken usernames or emails like successful ones.
+    pub uniform_responses: bool,
+}
+
+/// "Sign in with ..." settings; OIDC is off while `issuer_url` is unset.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcSettings {
+    pub issuer_url: Option<String>,
+    pub client_id: Option<String>,
+    #[serde(serialize_with = "redact::optional_secret")]
+    pub client_secret: Option<String>,
+    /// Defaults to `public_base_url` + `/api/auth/oidc/callback`.
+    pub redirect_url: Option<String>,
+    /// Browser-facing authorization endpoint, when it differs from the discovered one.
+    pub authorization_url: Option<String>,
+    pub provider_name: String,
+    pub scopes: String,
+    pub auto_register: bool,
+    pub link_verified_email: bool,
+    /// Accept an `http://` issuer and token endpoint, for a local mock provider only:
+    /// ID tokens are trusted because they arrive over TLS from the token endpoint.
+    pub allow_insecure_http: bool,
+}
+
+impl Default for OidcSettings {
+    fn default() -> Self {
+        OidcSettings {
+            issuer_url: None,
+            client_id: None,
+            client_secret: None,
+            redirect_url: None,
+            authorization_url: None,
+            provider_name: "SSO".to_string(),
+            scopes: "openid email profile".to_string(),
+            auto_register: true,
+            link_verified_email: false,
+            allow_insecure_http: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowSettings {
+    pub enabled: bool,
+    /// Key that signs challenges; random per process when unset.
+    #[serde(serialize_with = "redact::optional_secret")]
+    pub secret: Option<String>,
+    pub base_difficulty: u32,
+    pub max_difficulty: u32,
+    pub login_after_failures: i64,
+}
+
+impl Default for PowSettings {
+    fn default() -> Self {
+        PowSettings {
+            enabled: true,
+            secret: None,
+            base_difficulty: 16,
+            max_difficulty: 22,
+            login_after_failures: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeatherProviderKind {
+    #[default]
+    OpenMeteo,
+    Mock,
+}
+
+impl FromStr for WeatherProviderKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "open-meteo" | "openmeteo" => Ok(WeatherProviderKind::OpenMeteo),
+            "mock" => Ok(WeatherProviderKind::Mock),
+            _ => Err("expected open-meteo or mock".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherSettings {
+    pub provider: WeatherProviderKind,
+    /// How long weather per location is cached; 0 disables the cache.
+    pub cache_ttl_secs: u64,
+    pub default_city: String,
+    pub open_meteo_forecast_url: String,
+    pub open_meteo_geocoding_url: String,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        WeatherSettings {
+            provider: WeatherProviderKind::OpenMeteo,
+            cache_ttl_secs: 600,
+            default_city: "Copenhagen".to_string(),
+            open_meteo_forecast_url: "https://api.open-meteo.com/v1/forecast".to_string(),
+            open_meteo_geocoding_url: "https://geocoding-api.open-meteo.com/v1/search".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MailTransportKind {
+    /// Mail isn't sent; whatever needs it fails.
+    #[default]
+    None,
+    /// Writes whole messages, links included, to the log. Development only.
+    Log,
+    Smtp,
+}
+
+impl FromStr for MailTransportKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Ok(MailTransportKind::None),
+            "log" => Ok(MailTransportKind::Log),
+            "smtp" => Ok(MailTransportKind::Smtp),
+            _ => Err("expected none, log or smtp".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailSettings {
+    pub transport: MailTransportKind,
+    /// Sender, e.g. `WhoKnows <noreply@example.com>`.
+    pub from: String,
+    /// Relay for the smtp transport: `smtps://` for TLS, or `smtp://...?tls=required` for
+    /// STARTTLS, with any credentials in the user info.
+    #[serde(serialize_with = "redact::url")]
+    pub smtp_url: String,
+}
+
+impl Default for MailSettings {
+    fn default() -> Self {
+        MailSettings {
+            transport: MailTransportKind::None,
+            from: "WhoKnows <noreply@localhost>".to_string(),
+            smtp_url: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthSettings {
+    /// Free space the database's file system must have for `/readyz` to pass.
+    pub min_free_disk_mb: u64,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        HealthSettings {
+            min_free_disk_mb: 100,
+        }
+    }
+}
+
+// --- Environment and Validation ---
+
+/// Names a setting in error messages by both of the ways it can be given.
+struct Setting(&'static str, &'static str);
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (backend.{})", self.0, self.1)
+    }
+}
+
+/// A network, or a single address as a network of one.
+fn parse_network(entry: &str) -> Option<IpNet> {
+    entry
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+impl BackendSettings {
+    /// `trusted_proxies` as networks; `validate` has rejected entries that don't parse.
+    pub fn trusted_proxy_networks(&self) -> Vec<IpNet> {
+        self.trusted_proxies
+            .iter()
+            .filter_map(|entry| parse_network(entry))
+            .collect()
+    }
+
+    /// Scheme, host and port of `public_base_url`, the one origin the backend's CORS allows.
+    pub fn public_origin(&self) -> String {
+        let url = &self.public_base_url;
+        let authority = url.find("://").map_or(0, |i| i + 3);
+        match url[authority..].find('/') {
+            Some(path) => url[..authority + path].to_string(),
+            None => url.clone(),
+        }
+    }
+
+    pub(crate) fn apply_port_env(&mut self, env: &mut EnvLayer) {
+        env.parse(BACKEND_INTERNAL_PORT_KEY, &mut self.port);
+    }
+
+    pub(crate) fn apply_env(&mut self, env: &mut EnvLayer) {
+        self.apply_port_env(env);
+        env.string(DATABASE_URL_KEY, &mut self.database_url);
+        env.flag(MIGRATE_ON_STARTUP_KEY, &mut self.migrate_on_startup);
+        env.string(BUILD_VERSION_KEY, &mut self.build_version);
+        env.string(PUBLIC_BASE_URL_KEY, &mut self.public_base_url);
+        env.opt_string(INITIAL_ADMIN_KEY, &mut self.initial_admin);
+        env.list(TRUSTED_PROXIES_KEY, &mut self.trusted_proxies);
+
+        let session = &mut self.session;
+        env.string(SESSION_SECRET_KEY_KEY, &mut session.secret_key);
+        env.parse(
+            SESSION_IDLE_TIMEOUT_SECS_KEY,
+            &mut session.idle_timeout_secs,
+        );
+        env.parse(
+            SESSION_ABSOLUTE_TIMEOUT_SECS_KEY,
+            &mut session.absolute_timeout_secs,
+        );
+
+        let argon2 = &mut self.argon2;
+        env.parse(ARGON2_MEMORY_KIB_KEY, &mut argon2.memory_kib);
+        env.parse(ARGON2_ITERATIONS_KEY, &mut argon2.iterations);
+        env.parse(ARGON2_PARALLELISM_KEY, &mut argon2.parallelism);
+
+        let password = &mut self.password;
+        env.parse(PASSWORD_MIN_LENGTH_KEY, &mut password.min_length);
+        env.parse(PASSWORD_MIN_STRENGTH_KEY, &mut password.min_strength);
+        env.opt_path(BREACHED_PASSWORDS_FILE_KEY, &mut password.breached_file);
+
+        let registration = &mut self.registration;
+        env.parse(REGISTRATION_MODE_KEY, &mut registration.mode);
+        env.flag(
+            REGISTRATION_UNIFORM_RESPONSES_KEY,
+            &mut registration.uniform_responses,
+        );
+
+        let oidc = &mut self.oidc;
+        env.opt_string(OIDC_ISSUER_URL_KEY, &mut oidc.issuer_url);
+        env.opt_string(OIDC_CLIENT_ID_KEY, &mut oidc.client_id);
+        env.opt_string(OIDC_CLIENT_SECRET_KEY, &mut oidc.client_secret);
+        env.opt_string(OIDC_REDIRECT_URL_KEY, &mut oidc.redirect_url);
+        env.opt_string(OIDC_AUTHORIZATION_URL_KEY, &mut oidc.authorization_url);
+        env.string(OIDC_PROVIDER_NAME_KEY, &mut oidc.provider_name);
+        env.string(OIDC_SCOPES_KEY, &mut oidc.scopes);
+        env.flag(OIDC_AUTO_REGISTER_KEY, &mut oidc.auto_register);
+        env.flag(OIDC_LINK_VERIFIED_EMAIL_KEY, &mut oidc.link_verified_email);
+        env.flag(OIDC_ALLOW_INSECURE_HTTP_KEY, &mut oidc.allow_insecure_http);
+
+        let pow = &mut self.pow;
+        env.flag(POW_ENABLED_KEY, &mut pow.enabled);
+        env.opt_string(POW_SECRET_KEY, &mut pow.secret);
+        env.parse(POW_BASE_DIFFICULTY_KEY, &mut pow.base_difficulty);
+        env.parse(POW_MAX_DIFFICULTY_KEY, &mut pow.max_difficulty);
+        env.parse(POW_LOGIN_AFTER_FAILURES_KEY, &mut pow.login_after_failures);
+
+        let weather = &mut self.weather;
+        env.parse(WEATHER_PROVIDER_KEY, &mut weather.provider);
+        env.parse(WEATHER_CACHE_TTL_SECS_KEY, &mut weather.cache_ttl_secs);
+        env.string(WEATHER_DEFAULT_CITY_KEY, &mut weather.default_city);
+        env.string(
+            OPEN_METEO_FORECAST_URL_KEY,
+            &mut weather.open_meteo_forecast_url,
+        );
+        env.string(
+            OPEN_METEO_GEOCODING_URL_KEY,
+            &mut weather.open_meteo_geocoding_url,
+        );
+
+        let mail = &mut self.mail;
+        env.parse(MAIL_TRANSPORT_KEY, &mut mail.transport);
+        env.string(MAIL_FROM_KEY, &mut mail.from);
+        env.string(SMTP_URL_KEY, &mut mail.smtp_url);
+
+        env.parse(
+            HEALTH_MIN_FREE_DISK_MB_KEY,
+            &mut self.health.min_free_disk_mb,
+        );
+    }
+
+    pub(crate) fn validate(&mut self, environment: Environment, problems: &mut Vec<String>) {
+        if self.database_url.trim().is_empty() {
+            problems.push(format!(
+                "{} must be set",
+                Setting(DATABASE_URL_KEY, "database_url")
+            ));
+        }
+        self.public_base_url = self
+            .public_base_url
+            .trim()
+            .trim_end_matches('/')
+            .to_string();
+        if self.public_base_url.is_empty() {
+            if environment == Environment::Development {
+                self.public_base_url = DEV_PUBLIC_BASE_URL.to_string();
+            } else {
+                problems.push(format!(
+                    "{} must be set outside development; it is the origin CORS allows and \
+                     the address in mailed links",
+                    Setting(PUBLIC_BASE_URL_KEY, "public_base_url")
+                ));
+            }
+        } else {
+            check_url(
+                problems,
+                Setting(PUBLIC_BASE_URL_KEY, "public_base_url"),
+                &self.public_base_url,
+            );
+        }
+
+        for entry in &self.trusted_proxies {
+            if parse_network(entry).is_none() {
+                problems.push(format!(
+                    "{} has '{}', which is neither an IP address nor a network like 10.0.0.0/8",
+                    Setting(TRUSTED_PROXIES_KEY, "trusted_proxies"),
+                    entry
+                ));
+            }
+        }
+
+        let session = &self.session;
+        if session.secret_key_bytes().is_none() {
+            problems.push(format!(
+                "{} must be 64 hex digits (32 bytes)",
+                Setting(SESSION_SECRET_KEY_KEY, "session.secret_key")
+            ));
+        }
+        if session.idle_timeout_secs <= 0 {
+            problems.push(format!(
+                "{} must be a positive number of seconds",
+                Setting(SESSION_IDLE_TIMEOUT_SECS_KEY, "session.idle_timeout_secs")
+            ));
+        }
+        if session.absolute_timeout_secs <= 0 {
+            problems.push(format!(
+                "{} must be a positive number of seconds",
+                Setting(
+                    SESSION_ABSOLUTE_TIMEOUT_SECS_KEY,
+                    "session.absolute_timeout_secs"
+                )
+            ));
+        }
+
+        // The limits argon2::Params::new enforces.
+        let argon2 = &self.argon2;
+        if argon2.iterations < 1 {
+            problems.push(format!(
+                "{} must be at least 1",
+                Setting(ARGON2_ITERATIONS_KEY, "argon2.iterations")
+            ));
+        }
+        if !(1..(1 << 24)).contains(&argon2.parallelism) {
+            problems.push(format!(
+                "{} must be between 1 and 16777215",
+                Setting(ARGON2_PARALLELISM_KEY, "argon2.parallelism")
+            ));
+        }
+        if u64::from(argon2.memory_kib) < 8 * u64::from(argon2.parallelism) {
+            problems.push(format!(
+                "{} must be at least 8 KiB per lane",
+                Setting(ARGON2_MEMORY_KIB_KEY, "argon2.memory_kib")
+            ));
+        }
+
+        let password = &self.password;
+        if password.min_strength > MAX_PASSWORD_STRENGTH {
+            problems.push(format!(
+                "{} must be between 0 and {}",
+                Setting(PASSWORD_MIN_STRENGTH_KEY, "password.min_strength"),
+                MAX_PASSWORD_STRENGTH
+            ));
+        }
+        if let Some(path) = &password.breached_file {
+            if !path.is_file() {
+                problems.push(format!(
+                    "{} names {}, which is not a readable file",
+                    Setting(BREACHED_PASSWORDS_FILE_KEY, "password.breached_file"),
+                    path.display()
+                ));
+            }
+        }
+
+        let oidc = &mut self.oidc;
+        if let Some(issuer) = &mut oidc.issuer_url {
+            *issuer = issuer.trim_end_matches('/').to_string();
+            check_url(
+                problems,
+                Setting(OIDC_ISSUER_URL_KEY, "oidc.issuer_url"),
+                issuer,
+            );
+            if issuer.starts_with("http://") && !oidc.allow_insecure_http {
+                problems.push(format!(
+                    "{} must be an https:// URL; set {} only for a local test provider",
+                    Setting(OIDC_ISSUER_URL_KEY, "oidc.issuer_url"),
+                    OIDC_ALLOW_INSECURE_HTTP_KEY
+                ));
+            }
+            if oidc.client_id.is_none() {
+                problems.push(format!(
+                    "{} must be set when {} is",
+                    Setting(OIDC_CLIENT_ID_KEY, "oidc.client_id"),
+                    OIDC_ISSUER_URL_KEY
+                ));
+            }
+        }
+
+        let pow = &self.pow;
+        if pow.max_difficulty > MAX_POW_DIFFICULTY {
+            problems.push(format!(
+                "{} must be at most {}",
+                Setting(POW_MAX_DIFFICULTY_KEY, "pow.max_difficulty"),
+                MAX_POW_DIFFICULTY
+            ));
+        }
+        if pow.base_difficulty > pow.max_difficulty {
+            problems.push(format!(
+                "{} must not exceed {}",
+                Setting(POW_BASE_DIFFICULTY_KEY, "pow.base_difficulty"),
+                POW_MAX_DIFFICULTY_KEY
+            ));
+        }
+        if pow.login_after_failures < 0 {
+            problems.push(format!(
+                "{} must not be negative",
+                Setting(POW_LOGIN_AFTER_FAILURES_KEY, "pow.login_after_failures")
+            ));
+        }
+
+        let weather = &self.weather;
+        if weather.default_city.trim().is_empty() {
+            problems.push(format!(
+                "{} must not be empty",
+                Setting(WEATHER_DEFAULT_CITY_KEY, "weather.default_city")
+            ));
+        }
+        if weather.provider == WeatherProviderKind::OpenMeteo {
+            check_url(
+                problems,
+                Setting(
+                    OPEN_METEO_FORECAST_URL_KEY,
+                    "weather.open_meteo_forecast_url",
+                ),
+                &weather.open_meteo_forecast_url,
+            );
+            check_url(
+                problems,
+                Setting(
+                    OPEN_METEO_GEOCODING_URL_KEY,
+                    "weather.open_meteo_geocoding_url",
+                ),
+                &weather.open_meteo_geocoding_url,
+            );
+        }
+
+        let mail = &self.mail;
+        match mail.transport {
+            MailTransportKind::Log if environment != Environment::Development => {
+                problems.push(format!(
+                    "{} is log, which writes mail and its links to the log; it is only \
+                     allowed with WHOKNOWS_ENV=development",
+                    Setting(MAIL_TRANSPORT_KEY, "mail.transport")
+                ));
+            }
+            MailTransportKind::Smtp => {
+                if !(mail.smtp_url.starts_with("smtp://") || mail.smtp_url.starts_with("smtps://"))
+                {
+                    problems.push(format!(
+                        "{} must be an smtp:// or smtps:// URL when {} is smtp",
+                        Setting(SMTP_URL_KEY, "mail.smtp_url"),
+                        MAIL_TRANSPORT_KEY
+                    ));
+                }
+                if mail.from.trim().is_empty() {
+                    problems.push(format!(
+                        "{} must be set when {} is smtp",
+                        Setting(MAIL_FROM_KEY, "mail.from"),
+                        MAIL_TRANSPORT_KEY
+                    ));
+                }
+            }
+            MailTransportKind::None if self.registration.uniform_responses => {
+                problems.push(format!(
+                    "{} needs a {}, as registrants only learn the outcome by mail",
+                    Setting(
+                        REGISTRATION_UNIFORM_RESPONSES_KEY,
+                        "registration.uniform_responses"
+                    ),
+                    MAIL_TRANSPORT_KEY
+                ));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// config/src/env_layer.rs
+// --- Environment Layer ---
+// Overrides settings with environment variables. Unset and blank variables leave the
+// setting alone, since compose files pass `${VAR}` through even when it is empty.
+// Values that don't parse are recorded as problems rather than ignored.
+use std::env;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub(crate) struct EnvLayer<'a> {
+    problems: &'a mut Vec<String>,
+}
+
+impl<'a> EnvLayer<'a> {
+    pub(crate) fn new(problems: &'a mut Vec<String>) -> Self {
+        EnvLayer { problems }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        env::var(key)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    pub(crate) fn string(&mut self, key: &str, target: &mut String) {
+        if let Some(value) = self.get(key) {
+            *target = value;
+        }
+    }
+
+    pub(crate) fn opt_string(&mut self, key: &str, target: &mut Option<String>) {
+        if let Some(value) = self.get(key) {
+            *target = Some(value);
+        }
+    }
+
+    /// Comma-separated values, e.g. `en,da`.
+    pub(crate) fn list(&mut self, key: &str, target: &mut Vec<String>) {
+        if let Some(value) = self.get(key) {
+            *target = value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+
+    pub(crate) fn opt_path(&mut self, key: &str, target: &mut Option<PathBuf>) {
+        if let Some(value) = self.get(key) {
+            *target = Some(PathBuf::from(value));
+        }
+    }
+
+    pub(crate) fn parse<T>(&mut self, key: &str, target: &mut T)
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Some(value) = self.get(key) {
+            match value.parse() {
+                Ok(parsed) => *target = parsed,
+                Err(e) => self
+                    .problems
+                    .push(format!("{} has an invalid value '{}': {}", key, value, e)),
+            }
+        }
+    }
+
+    pub(crate) fn flag(&mut self, key: &str, target: &mut bool) {
+        if let Some(value) = self.get(key) {
+            match value.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => *target = true,
+                "0" | "false" | "no" => *target = false,
+                _ => self
+                    .problems
+                    .push(format!("{} must be true or false, not '{}'", key, value)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test uses its own variable names, as tests run in parallel in one process.
+
+    #[test]
+    fn parse_reports_invalid_values_and_keeps_the_default() {
+        env::set_var("WHOKNOWS_TEST_PARSE_PORT", "eighty");
+        let mut problems = Vec::new();
+        let mut port: u16 = 92;
+        EnvLayer::new(&mut problems).parse("WHOKNOWS_TEST_PARSE_PORT", &mut port);
+
+        assert_eq!(port, 92);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("WHOKNOWS_TEST_PARSE_PORT has an invalid value 'eighty'"));
+    }
+
+    #[test]
+    fn parse_applies_valid_values() {
+        env::set_var("WHOKNOWS_TEST_PARSE_VALID", " 8080 ");
+        let mut problems = Vec::new();
+        let mut port: u16 = 92;
+        EnvLayer::new(&mut problems).parse("WHOKNOWS_TEST_PARSE_VALID", &mut port);
+
+        assert_eq!(port, 8080);
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn flag_reports_values_other_than_true_or_false() {
+        env::set_var("WHOKNOWS_TEST_FLAG", "maybe");
+        let mut problems = Vec::new();
+        let mut enabled = true;
+        EnvLayer::new(&mut problems).flag("WHOKNOWS_TEST_FLAG", &mut enabled);
+
+        assert!(enabled);
+        assert_eq!(
+            problems,
+            ["WHOKNOWS_TEST_FLAG must be true or false, not 'maybe'"]
+        );
+    }
+
+    #[test]
+    fn unset_and_blank_variables_change_nothing() {
+        env::remove_var("WHOKNOWS_TEST_UNSET");
+        env::set_var("WHOKNOWS_TEST_BLANK", "  ");
+        let mut problems = Vec::new();
+        let mut layer = EnvLayer::new(&mut problems);
+        let mut port: u16 = 92;
+        let mut name = "default".to_string();
+        layer.parse("WHOKNOWS_TEST_UNSET", &mut port);
+        layer.string("WHOKNOWS_TEST_BLANK", &mut name);
+
+        assert_eq!(port, 92);
+        assert_eq!(name, "default");
+        assert!(problems.is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+// config/src/frontend.rs
+// --- Frontend Settings ---
+// What the frontend server reads at startup, under `[frontend]` in the TOML file. The
+// backend it proxies to is found through `backend.port` unless `backend_url` says
+// otherwise.
+use serde::{Deserialize, Serialize};
+
+use crate::check_url;
+use crate::env_layer::EnvLayer;
+
+const FRONTEND_INTERNAL_PORT_KEY: &str = "FRONTEND_INTERNAL_PORT";
+const FRONTEND_URL_KEY: &str = "FRONTEND_URL";
+const BACKEND_URL_KEY: &str = "BACKEND_URL";
+
+const DEFAULT_FRONTEND_PORT: u16 = 91;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontendSettings {
+    pub port: u16,
+    /// Origin the site is served from, allowed by CORS.
+    pub url: String,
+    /// Where `/api/` requests go; `http://backend:<backend.port>` when unset.
+    pub backend_url: Option<String>,
+}
+
+impl Default for FrontendSettings {
+    fn default() -> Self {
+        FrontendSettings {
+            port: DEFAULT_FRONTEND_PORT,
+            url: "http://localhost:8080".to_string(),
+            backend_url: None,
+        }
+    }
+}
+
+impl FrontendSettings {
+    pub(crate) fn apply_env(&mut self, env: &mut EnvLayer) {
+        env.parse(FRONTEND_INTERNAL_PORT_KEY, &mut self.port);
+        env.string(FRONTEND_URL_KEY, &mut self.url);
+        env.opt_string(BACKEND_URL_KEY, &mut self.backend_url);
+    }
+
+    pub(crate) fn validate(&mut self, problems: &mut Vec<String>) {
+        self.url = self.url.trim_end_matches('/').to_string();
+        check_url(problems, "FRONTEND_URL (frontend.url)", &self.url);
+        if let Some(url) = &self.backend_url {
+            check_url(problems, "BACKEND_URL (frontend.backend_url)", url);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+// config/src/metrics.rs
+// --- Metrics Label Policy ---
+// Every Prometheus label value starts a new time series, so values that clients
+// control are mapped onto a fixed set before they reach a metric. Languages and HTTP
+// methods go through allow-lists; anything else still gets a per-label cap, after
+// which new values are counted under "other". Shared by both services under
+// `[metrics]` in the TOML file.
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::env_layer::EnvLayer;
+
+const METRICS_LANGUAGES_KEY: &str = "METRICS_LANGUAGES";
+const METRICS_MAX_LABEL_VALUES_KEY: &str = "METRICS_MAX_LABEL_VALUES";
+
+/// Label value for everything outside an allow-list or past the cap.
+pub const OTHER_LABEL: &str = "other";
+
+const HTTP_METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSettings {
+    /// Search languages reported as themselves; the rest are "other".
+    pub languages: Vec<String>,
+    /// Distinct values any one label of any one metric may take.
+    pub max_label_values: usize,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        MetricsSettings {
+            // The languages `pages.language` allows.
+            languages: vec!["en".to_string(), "da".to_string()],
+            max_label_values: 100,
+        }
+    }
+}
+
+impl MetricsSettings {
+    pub(crate) fn apply_env(&mut self, env: &mut EnvLayer) {
+        env.list(METRICS_LANGUAGES_KEY, &mut self.languages);
+        env.parse(METRICS_MAX_LABEL_VALUES_KEY, &mut self.max_label_values);
+    }
+
+    pub(crate) fn validate(&mut self, problems: &mut Vec<String>) {
+        if self.max_label_values == 0 {
+            problems.push(
+                "METRICS_MAX_LABEL_VALUES (metrics.max_label_values) must be at least 1"
+                    .to_string(),
+            );
+        }
+    }
+}
+
+/// Applies [`MetricsSettings`] to label values. Cheap to call on every request.
+pub struct LabelPolicy {
+    languages: Vec<String>,
+    max_label_values: usize,
+    /// Values seen so far per `metric/label`.
+    seen: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl LabelPolicy {
+    pub fn new(settings: &MetricsSettings) -> Self {
+        LabelPolicy {
+            languages: settings.languages.clone(),
+            max_label_values: settings.max_label_values,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `language` if it is allow-listed, otherwise "other".
+    pub fn language<'a>(&self, language: &'a str) -> &'a str {
+        if self.languages.iter().any(|known| known == language) {
+            language
+        } else {
+            OTHER_LABEL
+        }
+    }
+
+    /// Standard HTTP methods as themselves, extension methods as "other".
+    pub fn method<'a>(&self, method: &'a str) -> &'a str {
+        if HTTP_METHODS.contains(&method) {
+            method
+        } else {
+            OTHER_LABEL
+        }
+    }
+
+    /// Passes `value` through while `metric`'s `label` has fewer than the maximum
+    /// number of distinct values, and maps new values to "other" after that. Meant as
+    /// a backstop for labels that should be bounded anyway, such as route patterns.
+    pub fn bounded<'a>(&self, metric: &str, label: &str, value: &'a str) -> &'a str {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let key = format!("{}/{}", metric, label);
+        let values = seen.entry(key).or_default();
+        if values.contains(value) {
+            return value;
+        }
+        if values.len() < self.max_label_values {
+            values.insert(value.to_string());
+            return value;
+        }
+        if values.insert(OTHER_LABEL.to_string()) {
+            log::warn!(
+                "Metric {} reached {} values for label '{}'; further values are counted as '{}'.",
+                metric,
+                self.max_label_values,
+                label,
+                OTHER_LABEL
+            );
+        }
+        OTHER_LABEL
+    }
+}
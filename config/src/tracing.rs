@@ -0,0 +1,97 @@
+// config/src/tracing.rs
+// --- Tracing Settings ---
+// Where OpenTelemetry spans go, under `[tracing]` in the TOML file and shared by both
+// services: nowhere by default, to an OTLP collector, or as JSON lines to stdout or a
+// file for local runs.
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::check_url;
+use crate::env_layer::EnvLayer;
+
+const TRACING_EXPORTER_KEY: &str = "TRACING_EXPORTER";
+const OTEL_EXPORTER_OTLP_ENDPOINT_KEY: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const TRACING_FILE_KEY: &str = "TRACING_FILE";
+const TRACING_SAMPLE_RATIO_KEY: &str = "TRACING_SAMPLE_RATIO";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TracingExporter {
+    /// Spans are not recorded, but `traceparent` headers are still passed on.
+    #[default]
+    None,
+    Otlp,
+    Stdout,
+    File,
+}
+
+impl FromStr for TracingExporter {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Ok(TracingExporter::None),
+            "otlp" => Ok(TracingExporter::Otlp),
+            "stdout" => Ok(TracingExporter::Stdout),
+            "file" => Ok(TracingExporter::File),
+            _ => Err("expected none, otlp, stdout or file".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingSettings {
+    pub exporter: TracingExporter,
+    /// Base URL of an OTLP/HTTP collector; `/v1/traces` is appended.
+    pub otlp_endpoint: String,
+    /// Where the `file` exporter appends spans, one JSON object per line.
+    pub file: Option<PathBuf>,
+    /// Share of new traces recorded, 0.0 to 1.0. Traces started upstream follow the
+    /// caller's decision.
+    pub sample_ratio: f64,
+}
+
+impl Default for TracingSettings {
+    fn default() -> Self {
+        TracingSettings {
+            exporter: TracingExporter::None,
+            otlp_endpoint: "http://localhost:4318".to_string(),
+            file: None,
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+impl TracingSettings {
+    pub(crate) fn apply_env(&mut self, env: &mut EnvLayer) {
+        env.parse(TRACING_EXPORTER_KEY, &mut self.exporter);
+        env.string(OTEL_EXPORTER_OTLP_ENDPOINT_KEY, &mut self.otlp_endpoint);
+        env.opt_path(TRACING_FILE_KEY, &mut self.file);
+        env.parse(TRACING_SAMPLE_RATIO_KEY, &mut self.sample_ratio);
+    }
+
+    pub(crate) fn validate(&mut self, problems: &mut Vec<String>) {
+        self.otlp_endpoint = self.otlp_endpoint.trim_end_matches('/').to_string();
+        if self.exporter == TracingExporter::Otlp {
+            check_url(
+                problems,
+                "OTEL_EXPORTER_OTLP_ENDPOINT (tracing.otlp_endpoint)",
+                &self.otlp_endpoint,
+            );
+        }
+        if self.exporter == TracingExporter::File && self.file.is_none() {
+            problems.push(
+                "TRACING_FILE (tracing.file) is required when TRACING_EXPORTER is file".to_string(),
+            );
+        }
+        if !(0.0..=1.0).contains(&self.sample_ratio) {
+            problems.push(format!(
+                "TRACING_SAMPLE_RATIO (tracing.sample_ratio) must be between 0 and 1, not {}",
+                self.sample_ratio
+            ));
+        }
+    }
+}
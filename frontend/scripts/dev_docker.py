@@ -2,7 +2,7 @@
    Builds and runs the Docker container for local development.
    - using .env.local.frontend 
    running: 
-   'docker build -t ${COMPOSE_PROJECT_NAME} . 
+   'docker build --build-context config=../config --build-context telemetry=../telemetry -t ${COMPOSE_PROJECT_NAME} . 
       && docker run --rm -d 
          --env-file .env.local.frontend 
          -p ${FRONTEND_INTERNAL_PORT}:${FRONTEND_EXTERNAL_PORT} 
@@ -18,8 +18,8 @@ FRONTEND_PORT = makefile_env_loader.get_frontend_port()
 BACKEND_PORT = makefile_env_loader.get_backend_port()
 
 def get_build_cmd(project_name_prm="whoknows.frontend.test") -> str | None:
-   """ docker build -t ${project_name} . """
-   return f"docker build -t {project_name_prm} ."
+   """ docker build --build-context config=../config --build-context telemetry=../telemetry -t ${project_name} . """
+   return f"docker build --build-context config=../config --build-context telemetry=../telemetry -t {project_name_prm} ."
 
 def get_run_cmd(frontend_port_prm="8080", image_name="whoknows.frontend.test") -> str | None:
    """ 
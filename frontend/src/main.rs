@@ -1,33 +1,60 @@
 use actix_files as files;
 use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
-use actix_web::{get, http, middleware, web, App, Error, HttpResponse, HttpServer, Responder};
+use actix_web::{
+    get, http, web, App, Error, HttpMessage, HttpRequest, HttpResponse, HttpServer,
+    Responder,
+};
 use actix_web::body::{EitherBody, MessageBody};
 use actix_cors::Cors;
 use awc::Client;
 use futures::future::{self, Either, LocalBoxFuture, Ready};
 use log::{info, error};
-use std::env;
+use std::sync::OnceLock;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 // --- Prometheus Monitoring ---
 use lazy_static::lazy_static;
 use prometheus::{
-    register_int_counter, register_int_counter_vec, Encoder, IntCounter, IntCounterVec, TextEncoder,
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder,
 };
 
 // Query string parser crate
 use querystring;
 
-// Define constants for environment variable names
-const FRONTEND_URL_KEY: &str = "FRONTEND_URL";
-const BACKEND_INTERNAL_PORT_KEY: &str = "BACKEND_INTERNAL_PORT";
-const FRONTEND_INTERNAL_PORT_KEY: &str = "FRONTEND_INTERNAL_PORT";
+// Shared configuration crate
+use whoknows_config::{Config, LabelPolicy, MetricsSettings};
+
+// Shared tracing crate
+use whoknows_telemetry::KeyValue;
 
 // --- Prometheus Metrics ---
 lazy_static! {
-    static ref HTTP_REQUESTS_TOTAL: IntCounter = register_int_counter!(
+    static ref HTTP_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
         "http_requests_total",
-        "Total number of HTTP requests handled by the frontend server"
+        "Total number of HTTP requests handled by the frontend server",
+        &["method", "route", "status"]
+    )
+    .unwrap();
+
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time from receiving a request to sending its response, including the backend",
+        &["method", "route", "status"]
+    )
+    .unwrap();
+
+    static ref HTTP_REQUESTS_IN_FLIGHT: IntGauge = register_int_gauge!(
+        "http_requests_in_flight",
+        "Requests received but not yet answered"
+    )
+    .unwrap();
+
+    static ref PROXY_UPSTREAM_DURATION: HistogramVec = register_histogram_vec!(
+        "proxy_upstream_duration_seconds",
+        "Time spent waiting on the backend for proxied /api/ requests",
+        &["method", "status"]
     )
     .unwrap();
 
@@ -45,7 +72,59 @@ lazy_static! {
     .unwrap();
 }
 
-// Request counter middleware
+// Route label for requests the proxy forwarded; the backend's own metrics break them
+// down by route.
+const PROXIED_ROUTE: &str = "proxied";
+// Route label for requests that matched no route, so random paths don't each create
+// a series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+// Client-sent proxy headers, dropped before proxying; the backend would otherwise
+// take a spoofed client address for the real one.
+const FORWARDING_HEADERS: &[&str] = &[
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-host",
+    "x-forwarded-proto",
+    "x-real-ip",
+];
+
+// Keeps client-controlled label values (methods, languages) from creating new series;
+// set from the `[metrics]` settings at startup.
+static LABEL_POLICY: OnceLock<LabelPolicy> = OnceLock::new();
+
+fn label_policy() -> &'static LabelPolicy {
+    LABEL_POLICY.get_or_init(|| LabelPolicy::new(&MetricsSettings::default()))
+}
+
+// Marks a request as answered by ApiProxy.
+struct Proxied;
+
+fn route_label(req: &HttpRequest) -> String {
+    if req.extensions().get::<Proxied>().is_some() {
+        PROXIED_ROUTE.to_string()
+    } else {
+        req.match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string())
+    }
+}
+
+// Counts a request as in flight until dropped, even if the client goes away first.
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        HTTP_REQUESTS_IN_FLIGHT.inc();
+        InFlight
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        HTTP_REQUESTS_IN_FLIGHT.dec();
+    }
+}
+
+// Request counter middleware: counts and times every request by method, matched route
+// pattern and status
 struct RequestCounter;
 
 impl<S, B> Transform<S, ServiceRequest> for RequestCounter
@@ -82,31 +161,80 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        // Increment counter for all requests
-        HTTP_REQUESTS_TOTAL.inc();
+        let started = Instant::now();
+        let in_flight = InFlight::start();
+        let method = req.method().to_string();
 
         let fut = self.service.call(req);
         Box::pin(async move {
-            let res = fut.await?;
-            Ok(res)
+            let result = fut.await;
+            drop(in_flight);
+
+            let (route, status) = match &result {
+                Ok(res) => (route_label(res.request()), res.status()),
+                Err(e) => (UNMATCHED_ROUTE.to_string(), e.as_response_error().status_code()),
+            };
+            let policy = label_policy();
+            let labels = [
+                policy.method(&method),
+                policy.bounded("http_requests_total", "route", &route),
+                policy.bounded("http_requests_total", "status", status.as_str()),
+            ];
+            HTTP_REQUESTS_TOTAL.with_label_values(&labels).inc();
+            HTTP_REQUEST_DURATION
+                .with_label_values(&labels)
+                .observe(started.elapsed().as_secs_f64());
+            result
         })
     }
 }
 
+/// How long `/api/health` waits for the backend's `/readyz`.
+const BACKEND_HEALTH_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Healthy only while the backend is ready, so uptime checks see the whole site.
 #[get("/api/health")]
-async fn health_check() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "ok",
-        "service": "frontend"
-    }))
+async fn health_check(req: HttpRequest, settings: web::Data<Config>) -> HttpResponse {
+    let client = Client::builder().timeout(BACKEND_HEALTH_TIMEOUT).finish();
+    let mut readyz = client.get(&format!("{}/readyz", settings.backend_url()));
+    if let Some(id) = whoknows_telemetry::request_id(&req) {
+        readyz = readyz.insert_header((whoknows_telemetry::REQUEST_ID_HEADER, id));
+    }
+    let (ready, backend) = match readyz.send().await {
+        Ok(mut res) => {
+            let ready = res.status().is_success();
+            let status = res.status().as_u16();
+            let body = res
+                .json::<serde_json::Value>()
+                .await
+                .unwrap_or_else(|_| serde_json::json!({ "status": status }));
+            (ready, body)
+        }
+        Err(e) => {
+            error!("Backend readiness check failed: {}", e);
+            (false, serde_json::json!({ "status": "unreachable" }))
+        }
+    };
+
+    if ready {
+        HttpResponse::Ok().json(serde_json::json!({
+            "status": "ok",
+            "service": "frontend",
+            "backend": backend
+        }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "degraded",
+            "service": "frontend",
+            "backend": backend
+        }))
+    }
 }
 
 #[get("/api/config")]
-async fn get_config() -> HttpResponse {
-    let backend_port = env::var(BACKEND_INTERNAL_PORT_KEY).unwrap_or_else(|_| "92".to_string());
-
+async fn get_config(settings: web::Data<Config>) -> HttpResponse {
     HttpResponse::Ok().json(serde_json::json!({
-        "BACKEND_URL": format!("http://backend:{}", backend_port)
+        "BACKEND_URL": settings.backend_url()
     }))
 }
 
@@ -132,10 +260,8 @@ async fn metrics() -> impl Responder {
 }
 
 #[get("/static/js/api_config.js")]
-async fn api_config() -> HttpResponse {
-    let backend_port = env::var(BACKEND_INTERNAL_PORT_KEY).unwrap_or_else(|_| "92".to_string());
-
-    let js_content = format!("window.BACKEND_URL = 'http://backend:{}';\n", backend_port);
+async fn api_config(settings: web::Data<Config>) -> HttpResponse {
+    let js_content = format!("window.BACKEND_URL = '{}';\n", settings.backend_url());
 
     HttpResponse::Ok()
         .content_type("application/javascript")
@@ -151,7 +277,9 @@ struct ApiProxy {
 impl ApiProxy {
     fn new(backend_url: String) -> Self {
         ApiProxy {
-            client: Client::default(),
+            // Redirects are the browser's business, e.g. the OIDC login sending it to the
+            // identity provider; following them here would fetch the provider's page.
+            client: Client::builder().disable_redirects().finish(),
             backend_url,
         }
     }
@@ -200,7 +328,6 @@ where
         if req.path().starts_with("/api/")
             && req.path() != "/api/health"
             && req.path() != "/api/config"
-            && req.path() != "/api/logout"
             && req.path() != "/api/metrics"
         {
             // 🚀 NEW: Handle search metrics
@@ -213,7 +340,7 @@ where
                 let lang = querystring::querify(query_params)
                     .iter()
                     .find(|(key, _)| *key == "language")
-                    .map(|(_, value)| *value)
+                    .map(|(_, value)| label_policy().language(value))
                     .unwrap_or("unknown");
 
                 SEARCH_QUERIES_BY_LANG.with_label_values(&[lang]).inc();
@@ -225,8 +352,10 @@ where
             let query = req.query_string().to_string();
             let method = req.method().clone();
             let headers = req.headers().clone();
+            let peer = req.peer_addr().map(|addr| addr.ip());
 
             let (request, payload) = req.into_parts();
+            request.extensions_mut().insert(Proxied);
 
             let backend_req_url = if query.is_empty() {
                 format!("{}{}", backend_url, path)
@@ -236,17 +365,46 @@ where
 
             info!("Proxying request to backend: {}", backend_req_url);
 
+            // The backend call gets its own span, and the backend continues from it.
+            let trace_cx = whoknows_telemetry::client_span(
+                &whoknows_telemetry::request_context(&request),
+                format!("proxy {}", method),
+                vec![
+                    KeyValue::new("http.request.method", method.to_string()),
+                    KeyValue::new("url.full", backend_req_url.clone()),
+                ],
+            );
+
+            let request_id = whoknows_telemetry::request_id(&request);
+
             Box::pin(async move {
+                let upstream_started = Instant::now();
+                let method_label = label_policy().method(method.as_str()).to_string();
                 let mut client_req = client.request(method, &backend_req_url).no_decompress();
 
                 for (header_name, header_value) in headers.iter().filter(|(h, _)| {
                     *h != http::header::HOST
                         && *h != http::header::CONNECTION
                         && *h != http::header::CONTENT_LENGTH
+                        && !FORWARDING_HEADERS.contains(&h.as_str())
                 }) {
                     client_req =
                         client_req.insert_header((header_name.clone(), header_value.clone()));
                 }
+                // The backend trusts this from us, so it only ever names the real peer
+                if let Some(peer) = peer {
+                    client_req = client_req
+                        .insert_header((http::header::X_FORWARDED_FOR, peer.to_string()));
+                }
+                // Replaces any traceparent the browser sent
+                for (header_name, header_value) in whoknows_telemetry::inject(&trace_cx) {
+                    client_req = client_req.insert_header((header_name, header_value));
+                }
+                // The backend logs under the same ID, whether the browser sent one or not
+                if let Some(id) = &request_id {
+                    client_req = client_req
+                        .insert_header((whoknows_telemetry::REQUEST_ID_HEADER, id.as_str()));
+                }
 
                 let backend_response = client_req.send_stream(payload).await;
 
@@ -262,7 +420,19 @@ where
                                 .insert_header((header_name.clone(), header_value.clone()));
                         }
 
-                        let bytes = res.body().await?;
+                        let bytes = res.body().await;
+                        whoknows_telemetry::end_http_span(&trace_cx, res.status().as_u16());
+                        let bytes = bytes?;
+                        PROXY_UPSTREAM_DURATION
+                            .with_label_values(&[
+                                &method_label,
+                                label_policy().bounded(
+                                    "proxy_upstream_duration_seconds",
+                                    "status",
+                                    res.status().as_str(),
+                                ),
+                            ])
+                            .observe(upstream_started.elapsed().as_secs_f64());
 
                         Ok(ServiceResponse::new(
                             request,
@@ -271,6 +441,10 @@ where
                     }
                     Err(e) => {
                         error!("Backend request error: {}", e);
+                        whoknows_telemetry::end_failed_span(&trace_cx, e.to_string());
+                        PROXY_UPSTREAM_DURATION
+                            .with_label_values(&[&method_label, "error"])
+                            .observe(upstream_started.elapsed().as_secs_f64());
                         let error_response = HttpResponse::ServiceUnavailable()
                             .content_type("application/json")
                             .body(format!(
@@ -298,37 +472,58 @@ where
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
 
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let settings = match Config::load(whoknows_config::Service::Frontend) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    whoknows_telemetry::init_logging(&settings.log, settings.log_format, "whoknows-frontend");
+    let _ = LABEL_POLICY.set(LabelPolicy::new(&settings.metrics));
+
+    let telemetry = match whoknows_telemetry::init(&settings.tracing, "whoknows-frontend") {
+        Ok(telemetry) => telemetry,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let frontend_port = env::var(FRONTEND_INTERNAL_PORT_KEY).unwrap_or_else(|_| "91".to_string());
-    let backend_port = env::var(BACKEND_INTERNAL_PORT_KEY).unwrap_or_else(|_| "92".to_string());
+    let frontend_port = settings.frontend.port;
+    let backend_url = settings.backend_url();
+    let frontend_url = settings.frontend.url.clone();
 
     info!("Starting server at http://0.0.0.0:{}", frontend_port);
-    info!("Using backend at http://backend:{}", backend_port);
+    info!("Using backend at {}", backend_url);
 
-    let backend_url = format!("http://backend:{}", backend_port);
+    let settings = web::Data::new(settings);
 
-    HttpServer::new(move || {
+    let result = HttpServer::new(move || {
         App::new()
-            .wrap(middleware::Logger::default())
-            .wrap(RequestCounter)
+            .app_data(settings.clone())
             .wrap(ApiProxy::new(backend_url.clone()))
+            .wrap(RequestCounter) // Outside the proxy, so proxied requests are counted too
+            // Inside the trace, so preflights and rejected origins get a span and ID
             .wrap(
                 Cors::default()
-                    .allowed_origin(
-                        &std::env::var(FRONTEND_URL_KEY)
-                            .unwrap_or_else(|_| "http://localhost:8080".into()),
-                    )
+                    .allowed_origin(&frontend_url)
                     .allowed_methods(vec!["GET", "POST"])
                     .allowed_headers(vec![
                         http::header::AUTHORIZATION,
                         http::header::ACCEPT,
                     ])
                     .allowed_header(http::header::CONTENT_TYPE)
-                    .expose_headers(&[http::header::CONTENT_DISPOSITION])
+                    .expose_headers(&[
+                        http::header::CONTENT_DISPOSITION,
+                        whoknows_telemetry::REQUEST_ID_HEADER,
+                    ])
                     .supports_credentials()
                     .max_age(3600),
             )
+            .wrap(whoknows_telemetry::RequestId) // Inside the trace, so log lines name it
+            .wrap(whoknows_telemetry::TraceRequests) // Outermost, so every span nests under it
             .service(health_check)
             .service(get_config)
             .service(api_config)
@@ -344,5 +539,8 @@ async fn main() -> std::io::Result<()> {
     })
     .bind(format!("0.0.0.0:{}", frontend_port))?
     .run()
-    .await
+    .await;
+
+    telemetry.shutdown();
+    result
 }
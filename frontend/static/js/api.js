@@ -4,11 +4,100 @@
  * @url http://<Container-Service-Name>:${BACKEND_INTERNAL_PORT}
  * @url fx. http://backend:92
  *
- * @note THIS CLIENT DOES NOT IMPLEMENT CSRF PROTECTION.
- * Ensure your backend API either uses alternative security measures (like stateless token auth)
- * or that you understand and accept the risks of CSRF attacks if using session cookies.
+ * @note State-changing requests go through csrfFetch, which sends the session's
+ * CSRF token in the X-CSRF-Token header the backend requires.
  */
 class ApiClient {
+  constructor() {
+    this.csrfToken = null;
+  }
+
+  /**
+   * Get the CSRF token of the current session, fetching it when not cached.
+   * @param {boolean} refresh - Ignore the cached token
+   * @returns {Promise<string|null>}
+   */
+  async getCsrfToken(refresh = false) {
+    if (this.csrfToken && !refresh) {
+      return this.csrfToken;
+    }
+    try {
+      const response = await fetch("/api/csrf-token", { credentials: "include" });
+      if (!response.ok) {
+        throw new Error(`HTTP error! status: ${response.status}`);
+      }
+      this.csrfToken = (await response.json()).csrf_token;
+    } catch (error) {
+      console.error("CSRF token error:", error);
+      this.csrfToken = null;
+    }
+    return this.csrfToken;
+  }
+
+  /**
+   * fetch() for POST/PUT/DELETE requests: adds the CSRF token, and when the backend
+   * rejects it (the token changes at login) fetches a new one and retries once.
+   */
+  async csrfFetch(url, options = {}) {
+    const send = async (token) =>
+      fetch(url, {
+        ...options,
+        headers: { ...(options.headers || {}), "X-CSRF-Token": token || "" },
+        credentials: "include",
+      });
+
+    let response = await send(await this.getCsrfToken());
+    if (response.status === 403) {
+      const body = await response.clone().json().catch(() => ({}));
+      if (body.error === "Invalid or missing CSRF token") {
+        response = await send(await this.getCsrfToken(true));
+      }
+    }
+    return response;
+  }
+
+  /**
+   * Fetch a proof-of-work challenge and solve it.
+   * @param {string} purpose - "login" or "register"
+   * @returns {Promise<Object>} - Fields to merge into the request body:
+   *   { pow_challenge: string, pow_solution: string }, or {} when the backend has
+   *   proof of work switched off
+   */
+  async proofOfWork(purpose) {
+    try {
+      const response = await fetch(
+        `/api/pow/challenge?purpose=${encodeURIComponent(purpose)}`,
+        { credentials: "include" }
+      );
+      if (!response.ok) {
+        throw new Error(`HTTP error! status: ${response.status}`);
+      }
+      const challenge = await response.json();
+      if (!challenge.enabled) {
+        return {};
+      }
+      return {
+        pow_challenge: challenge.challenge,
+        pow_solution: await ProofOfWork.solve(challenge.challenge, challenge.difficulty),
+      };
+    } catch (error) {
+      console.error("Proof of work error:", error);
+      return {};
+    }
+  }
+
+  /**
+   * Whether the backend rejected a request for a missing or unusable proof-of-work
+   * solution (codes pow_required, pow_invalid, pow_expired, pow_reused).
+   */
+  async needsProofOfWork(response) {
+    if (response.status !== 428 && response.status !== 400) {
+      return false;
+    }
+    const body = await response.clone().json().catch(() => ({}));
+    return typeof body.code === "string" && body.code.startsWith("pow_");
+  }
+
   /**
    * Perform a search query
    * @param {string} query - The search query
@@ -47,6 +136,124 @@ class ApiClient {
     }
   }
 
+  /**
+   * Get current weather and a daily forecast.
+   * @param {Object} [params] - { city } or { lat, lon }, plus optional { days } (1-7);
+   *   without a location the backend's default city is used
+   * @returns {Promise<Object>} - Promise resolving to:
+   *   {
+   *     data: {
+   *       location: { name, country, latitude, longitude },
+   *       current: { time, temperature_c, description, ... },
+   *       forecast: [{ date, temperature_min_c, temperature_max_c, description, ... }],
+   *       provider: string,
+   *       cached: boolean
+   *     }
+   *   }
+   *   or { error: string } on failure
+   */
+  async weather(params = {}) {
+    try {
+      const query = new URLSearchParams(
+        Object.entries(params).filter(([, value]) => value !== undefined && value !== "")
+      );
+      const response = await fetch(`/api/weather?${query}`, { credentials: "include" });
+      const body = await response.json();
+      if (!response.ok) {
+        return { error: body.error || `HTTP error! status: ${response.status}` };
+      }
+      return body;
+    } catch (error) {
+      console.error("Weather error:", error);
+      return { error: "Weather service unavailable" };
+    }
+  }
+
+  /**
+   * City suggestions for the weather city field.
+   * @param {string} prefix - What the user typed so far
+   * @returns {Promise<Array>} - Promise resolving to
+   *   [{ id, name, country_code, admin1_code, latitude, longitude, population }]
+   */
+  async citySuggestions(prefix) {
+    try {
+      const response = await fetch(
+        `/api/weather/cities?prefix=${encodeURIComponent(prefix)}`,
+        { credentials: "include" }
+      );
+      if (!response.ok) {
+        throw new Error(`HTTP error! status: ${response.status}`);
+      }
+      return (await response.json()).cities || [];
+    } catch (error) {
+      console.error("City suggestions error:", error);
+      return [];
+    }
+  }
+
+  /**
+   * Server diagnostics for administrators.
+   * @returns {Promise<Object>} - Promise resolving to:
+   *   {
+   *     build_version: string,
+   *     started_at: string,
+   *     uptime_secs: number,
+   *     database: { size_bytes, page_size, page_count, free_pages, migration_version },
+   *     pool: { size, idle, max_connections },
+   *     config: Object (effective configuration, secrets redacted)
+   *   }
+   *   or { error: string } on failure
+   */
+  async diagnostics() {
+    try {
+      const response = await fetch("/api/admin/diagnostics", { credentials: "include" });
+      const body = await response.json();
+      if (!response.ok) {
+        return { error: body.error || `HTTP error! status: ${response.status}` };
+      }
+      return body;
+    } catch (error) {
+      console.error("Diagnostics error:", error);
+      return { error: "Diagnostics unavailable" };
+    }
+  }
+
+  /**
+   * Ask the backend who may register.
+   * @returns {Promise<Object>} - Promise resolving to:
+   *   { mode: "open" | "invite-only" | "closed" }
+   */
+  async registrationInfo() {
+    try {
+      const response = await fetch("/api/registration", { credentials: "include" });
+      if (!response.ok) {
+        throw new Error(`HTTP error! status: ${response.status}`);
+      }
+      return await response.json();
+    } catch (error) {
+      console.error("Registration info error:", error);
+      return { mode: "open" };
+    }
+  }
+
+  /**
+   * Ask the backend whether "Sign in with ..." (OIDC) is available.
+   * @returns {Promise<Object>} - Promise resolving to:
+   *   { enabled: boolean, provider_name?: string, login_url?: string }
+   */
+  async oidcInfo() {
+    try {
+      const response = await fetch("/api/auth/oidc", { credentials: "include" });
+      if (!response.ok) {
+        throw new Error(`HTTP error! status: ${response.status}`);
+      }
+      return await response.json();
+    } catch (error) {
+      console.error("OIDC info error:", error);
+      return { enabled: false };
+    }
+  }
+
   /**
    * Attempt to log in a user by sending JSON data.
    * @param {string} username - The username
@@ -77,18 +284,25 @@ class ApiClient {
         password: password,
       };
 
-      const response = await fetch(url, {
-        method: "POST",
-        headers: {
-          // *** Set Content-Type to application/json ***
-          "Content-Type": "application/json",
-          // Optional: Indicate that we expect a JSON response back
-          Accept: "application/json",
-        },
-        // *** Stringify the login data object for the body ***
-        body: JSON.stringify(loginData),
-        credentials: "include", // Send cookies with the request
-      });
+      const send = async (data) =>
+        this.csrfFetch(url, {
+          method: "POST",
+          headers: {
+            // *** Set Content-Type to application/json ***
+            "Content-Type": "application/json",
+            // Optional: Indicate that we expect a JSON response back
+            Accept: "application/json",
+          },
+          // *** Stringify the login data object for the body ***
+          body: JSON.stringify(data),
+          credentials: "include", // Send cookies with the request
+        });
+
+      let response = await send(loginData);
+      // After repeated failures the backend wants a proof-of-work solution too.
+      if (await this.needsProofOfWork(response)) {
+        response = await send({ ...loginData, ...(await this.proofOfWork("login")) });
+      }
 
       // --- Start: Improved Error Handling (Optional but recommended) ---
       if (!response.ok) {
@@ -147,7 +361,7 @@ class ApiClient {
   async logout() {
     try {
       const url = `/api/logout`;
-      const response = await fetch(url, {
+      const response = await this.csrfFetch(url, {
         method: "POST",
         headers: {
           // No specific headers needed usually for logout, unless backend requires them
@@ -181,11 +395,14 @@ class ApiClient {
    * @param {string} userData.email - User's email address
    * @param {string} userData.password - User's password
    * @param {string} userData.password2 - Password confirmation (must match password)
+   * @param {string} [userData.invite_code] - Invite code, required in invite-only mode
    * @returns {Promise<Object>} - Promise resolving to:
    *   {
    *     success: true,
+   *     status: 201 | 202,
    *     message: "User registered successfully"
    *   }
+   *   where 202 means the registration was received and the outcome goes out by mail
    *
    * On error:
    *   {
@@ -196,17 +413,22 @@ class ApiClient {
   async register(userData) {
     try {
       const url = `/api/register`;
-      const bodyData = JSON.stringify(userData);
+      const send = async () =>
+        this.csrfFetch(url, {
+          method: "POST",
+          headers: {
+            "Content-Type": "application/json",
+            Accept: "application/json",
+          },
+          body: JSON.stringify({ ...userData, ...(await this.proofOfWork("register")) }),
+          credentials: "include",
+        });
 
-      const response = await fetch(url, {
-        method: "POST",
-        headers: {
-          "Content-Type": "application/json",
-          Accept: "application/json",
-        },
-        body: bodyData,
-        credentials: "include",
-      });
+      let response = await send();
+      // A challenge can expire while the form is being corrected; solve a fresh one.
+      if (await this.needsProofOfWork(response)) {
+        response = await send();
+      }
 
       if (!response.ok) {
         let errorData = { message: `HTTP error! status: ${response.status}` };
@@ -222,7 +444,7 @@ class ApiClient {
         throw error;
       }
 
-      return await response.json();
+      return { ...(await response.json()), status: response.status };
     } catch (error) {
       console.error(
         "Registration error:",
@@ -0,0 +1,53 @@
+document.addEventListener("DOMContentLoaded", async () => {
+  const container = document.getElementById("diagnostics");
+  container.textContent = "Loading diagnostics...";
+
+  const response = await api.diagnostics();
+  if (response.error) {
+    container.textContent = response.error;
+    return;
+  }
+  displayDiagnostics(response);
+
+  function element(tag, className, text) {
+    const el = document.createElement(tag);
+    if (className) el.className = className;
+    if (text !== undefined) el.textContent = text;
+    return el;
+  }
+
+  function duration(secs) {
+    const days = Math.floor(secs / 86400);
+    const hours = Math.floor((secs % 86400) / 3600);
+    const minutes = Math.floor((secs % 3600) / 60);
+    return days > 0 ? `${days}d ${hours}h ${minutes}m` : `${hours}h ${minutes}m`;
+  }
+
+  function size(bytes) {
+    if (bytes >= 1024 * 1024) return `${(bytes / (1024 * 1024)).toFixed(1)} MiB`;
+    if (bytes >= 1024) return `${(bytes / 1024).toFixed(1)} KiB`;
+    return `${bytes} B`;
+  }
+
+  function displayDiagnostics(data) {
+    const { database, pool } = data;
+    container.replaceChildren();
+
+    const summary = element("dl", "diagnostics-summary");
+    const rows = [
+      ["Build version", data.build_version],
+      ["Running since", `${new Date(data.started_at).toLocaleString()} (${duration(data.uptime_secs)})`],
+      ["Database size", `${size(database.size_bytes)}, ${database.free_pages} free pages`],
+      ["Migration version", database.migration_version === null ? "none (schema file)" : database.migration_version],
+      ["Connections", `${pool.size} open, ${pool.idle} idle, at most ${pool.max_connections}`],
+    ];
+    rows.forEach(([label, value]) => {
+      summary.appendChild(element("dt", null, label));
+      summary.appendChild(element("dd", null, String(value)));
+    });
+    container.appendChild(summary);
+
+    container.appendChild(element("h3", null, "Effective configuration"));
+    container.appendChild(element("pre", "diagnostics-config", JSON.stringify(data.config, null, 2)));
+  }
+});
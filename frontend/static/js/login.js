@@ -2,6 +2,34 @@ document.addEventListener('DOMContentLoaded', () => {
   const loginForm = document.getElementById('login-form');
   const errorMessage = document.getElementById('error-message');
   const errorContent = document.getElementById('error-content');
+
+  // Errors reported by the OIDC callback come back as ?error=<code>
+  const OIDC_ERRORS = {
+    oidc_provider_error: 'The sign-in provider reported an error.',
+    oidc_provider_unavailable: 'The sign-in provider could not be reached. Please try again later.',
+    oidc_state_mismatch: 'The sign-in attempt expired. Please try again.',
+    oidc_token_error: 'The sign-in provider rejected the login. Please try again.',
+    oidc_invalid_id_token: 'The sign-in provider returned an invalid response.',
+    oidc_missing_email: 'The sign-in provider did not share your email address.',
+    oidc_email_unverified: 'The sign-in provider has not verified your email address. Verify it there, then try again.',
+    oidc_account_exists: 'An account with this email already exists. Log in with your password first, then use single sign-on to link it.',
+    oidc_registration_disabled: 'No account is linked to this login.',
+    oidc_registration_conflict: 'Your account could not be created because another sign-up got in the way. Please try again.',
+    oidc_internal: 'Something went wrong during sign-in. Please try again.',
+  };
+  const errorCode = new URLSearchParams(window.location.search).get('error');
+  if (errorCode) {
+    showError(OIDC_ERRORS[errorCode] || 'Sign-in failed. Please try again.');
+  }
+
+  api.oidcInfo().then((info) => {
+    if (info.enabled) {
+      const link = document.getElementById('oidc-login-link');
+      link.textContent = `Sign in with ${info.provider_name}`;
+      link.href = info.login_url;
+      document.getElementById('oidc-login').style.display = 'block';
+    }
+  });
   
   loginForm.addEventListener('submit', async (e) => {
     e.preventDefault();
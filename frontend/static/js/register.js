@@ -2,6 +2,21 @@ document.addEventListener("DOMContentLoaded", () => {
   const registerForm = document.getElementById("register-form");
   const errorMessage = document.getElementById("error-message");
   const errorContent = document.getElementById("error-content");
+  const notice = document.getElementById("notice");
+  const noticeContent = document.getElementById("notice-content");
+  let registrationMode = "open";
+
+  api.registrationInfo().then((info) => {
+    registrationMode = info.mode;
+    if (registrationMode === "invite-only") {
+      document
+        .querySelectorAll(".invite-field")
+        .forEach((field) => (field.style.display = ""));
+    } else if (registrationMode === "closed") {
+      showError("Registration is currently closed.");
+      registerForm.querySelector('input[type="submit"]').disabled = true;
+    }
+  });
 
   registerForm.addEventListener("submit", async (e) => {
     e.preventDefault();
@@ -10,6 +25,7 @@ document.addEventListener("DOMContentLoaded", () => {
     const email = registerForm.email.value.trim();
     const password = registerForm.password.value;
     const password2 = registerForm.password2.value;
+    const inviteCode = registerForm.invite_code.value.trim();
 
     // Simple validation
     if (!username) {
@@ -32,6 +48,11 @@ document.addEventListener("DOMContentLoaded", () => {
       return;
     }
 
+    if (registrationMode === "invite-only" && !inviteCode) {
+      showError("You need an invite code to register");
+      return;
+    }
+
     try {
       // In a real application, you would use the API client:
 
@@ -40,11 +61,18 @@ document.addEventListener("DOMContentLoaded", () => {
         email,
         password,
         password2,
+        invite_code: inviteCode || undefined,
       });
 
-      if (response.success) {
-        // Redirect to login page
+      if (response.success && response.status === 201) {
+        // Account created: redirect to login page
         window.location.href = "/login.html";
+      } else if (response.success) {
+        // 202 in uniform-response mode: the outcome arrives by mail
+        errorMessage.style.display = "none";
+        noticeContent.textContent = response.message;
+        notice.style.display = "block";
+        registerForm.reset();
       } else {
         showError(response.error || "Registration failed. Please try again.");
       }
@@ -55,6 +83,7 @@ document.addEventListener("DOMContentLoaded", () => {
   });
 
   function showError(message) {
+    notice.style.display = "none";
     errorContent.textContent = message;
     errorMessage.style.display = "block";
   }
@@ -0,0 +1,102 @@
+document.addEventListener("DOMContentLoaded", () => {
+  const cityInput = document.getElementById("city-input");
+  const cityButton = document.getElementById("city-button");
+  const weatherContainer = document.getElementById("weather");
+
+  const urlParams = new URLSearchParams(window.location.search);
+  cityInput.value = urlParams.get("city") || "";
+  showWeather(cityInput.value.trim());
+
+  cityInput.addEventListener("keypress", (event) => {
+    if (event.key === "Enter") {
+      makeWeatherRequest();
+    }
+  });
+  cityButton.addEventListener("click", makeWeatherRequest);
+
+  // Suggest cities while typing, once the user pauses.
+  const suggestions = document.getElementById("city-suggestions");
+  let suggestTimer = null;
+  cityInput.addEventListener("input", () => {
+    clearTimeout(suggestTimer);
+    const prefix = cityInput.value.trim();
+    if (prefix.length < 2) {
+      suggestions.replaceChildren();
+      return;
+    }
+    suggestTimer = setTimeout(async () => {
+      const cities = await api.citySuggestions(prefix);
+      suggestions.replaceChildren(
+        ...cities.map((city) => {
+          const option = document.createElement("option");
+          option.value = city.name;
+          option.label = `${city.name}, ${city.country_code}`;
+          return option;
+        })
+      );
+    }, 250);
+  });
+
+  function makeWeatherRequest() {
+    const city = cityInput.value.trim();
+    const url = new URL(window.location.href);
+    if (city) {
+      url.searchParams.set("city", city);
+    } else {
+      url.searchParams.delete("city");
+    }
+    window.history.pushState({}, "", url);
+    showWeather(city);
+  }
+
+  async function showWeather(city) {
+    weatherContainer.textContent = "Loading weather...";
+
+    const response = await api.weather({ city });
+    if (response.error) {
+      weatherContainer.textContent = response.error;
+      return;
+    }
+    displayWeather(response.data);
+  }
+
+  // Built with textContent rather than innerHTML, since city names come from outside.
+  function element(tag, className, text) {
+    const el = document.createElement(tag);
+    if (className) el.className = className;
+    if (text !== undefined) el.textContent = text;
+    return el;
+  }
+
+  const degrees = (value) => (value === null || value === undefined ? "–" : `${Math.round(value)}°C`);
+
+  function displayWeather(data) {
+    const { location, current, forecast } = data;
+    weatherContainer.replaceChildren();
+
+    const place = location.country ? `${location.name}, ${location.country}` : location.name;
+    weatherContainer.appendChild(element("h2", "weather-location", place));
+
+    const now = element("div", "weather-current");
+    now.appendChild(element("div", "weather-temperature", degrees(current.temperature_c)));
+    now.appendChild(element("div", "weather-description", current.description));
+    const details = [];
+    if (current.apparent_temperature_c !== null) details.push(`Feels like ${degrees(current.apparent_temperature_c)}`);
+    if (current.humidity_percent !== null) details.push(`Humidity ${Math.round(current.humidity_percent)}%`);
+    if (current.wind_speed_kmh !== null) details.push(`Wind ${Math.round(current.wind_speed_kmh)} km/h`);
+    now.appendChild(element("div", "weather-details", details.join(" · ")));
+    weatherContainer.appendChild(now);
+
+    const days = element("div", "weather-forecast");
+    forecast.forEach((day) => {
+      const item = element("div", "weather-day");
+      item.appendChild(element("div", "weather-day-date", day.date));
+      item.appendChild(element("div", "weather-day-description", day.description || ""));
+      item.appendChild(
+        element("div", "weather-day-temperature", `${degrees(day.temperature_min_c)} / ${degrees(day.temperature_max_c)}`)
+      );
+      days.appendChild(item);
+    });
+    weatherContainer.appendChild(days);
+  }
+});
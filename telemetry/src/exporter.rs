@@ -0,0 +1,114 @@
+// telemetry/src/exporter.rs
+// --- JSON Lines Exporter ---
+// Writes each finished span as one JSON object per line, to stdout or appended to a
+// file, for local runs without a collector. `grep <trace_id>` then shows one request
+// across both services.
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use opentelemetry::trace::Status;
+use opentelemetry::Value;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+
+pub(crate) struct JsonLinesExporter {
+    out: Mutex<Box<dyn Write + Send>>,
+    service_name: Option<String>,
+}
+
+impl fmt::Debug for JsonLinesExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLinesExporter").finish_non_exhaustive()
+    }
+}
+
+impl JsonLinesExporter {
+    pub(crate) fn stdout() -> Self {
+        JsonLinesExporter {
+            out: Mutex::new(Box::new(io::stdout())),
+            service_name: None,
+        }
+    }
+
+    pub(crate) fn file(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open trace file {}: {}", path.display(), e))?;
+        Ok(JsonLinesExporter {
+            out: Mutex::new(Box::new(file)),
+            service_name: None,
+        })
+    }
+
+    fn to_json(&self, span: &SpanData) -> serde_json::Value {
+        let attributes: serde_json::Map<String, serde_json::Value> = span
+            .attributes
+            .iter()
+            .map(|kv| (kv.key.to_string(), value_to_json(&kv.value)))
+            .collect();
+        let (status, error) = match &span.status {
+            Status::Unset => ("unset", None),
+            Status::Ok => ("ok", None),
+            Status::Error { description } => ("error", Some(description.to_string())),
+        };
+        let parent_span_id = span.parent_span_id.to_string();
+        serde_json::json!({
+            "service": self.service_name,
+            "trace_id": span.span_context.trace_id().to_string(),
+            "span_id": span.span_context.span_id().to_string(),
+            "parent_span_id": (span.parent_span_id != opentelemetry::SpanId::INVALID)
+                .then_some(parent_span_id),
+            "name": span.name,
+            "kind": format!("{:?}", span.span_kind).to_lowercase(),
+            "start_unix_nano": unix_nanos(span.start_time),
+            "duration_ms": span
+                .end_time
+                .duration_since(span.start_time)
+                .unwrap_or_default()
+                .as_secs_f64() * 1000.0,
+            "status": status,
+            "error": error,
+            "attributes": attributes,
+        })
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Bool(b) => serde_json::Value::from(*b),
+        Value::I64(i) => serde_json::Value::from(*i),
+        Value::F64(f) => serde_json::Value::from(*f),
+        other => serde_json::Value::from(other.as_str().into_owned()),
+    }
+}
+
+impl SpanExporter for JsonLinesExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        for span in &batch {
+            writeln!(out, "{}", self.to_json(span))
+                .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        }
+        out.flush()
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.service_name = resource
+            .get(&opentelemetry::Key::from_static_str("service.name"))
+            .map(|value| value.as_str().into_owned());
+    }
+}
@@ -0,0 +1,136 @@
+// telemetry/src/lib.rs
+// --- Shared Tracing ---
+// OpenTelemetry setup for the backend and frontend. `init` installs the exporter chosen
+// in `[tracing]` and the W3C trace context propagator, `TraceRequests` opens a server
+// span per request that continues any incoming `traceparent`, and `client_span` and
+// `inject` let outgoing calls join the same trace. Logging and request IDs live here
+// too, so log lines can name the trace they belong to.
+use std::collections::HashMap;
+
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use whoknows_config::{TracingExporter, TracingSettings};
+
+mod exporter;
+mod logging;
+mod middleware;
+mod request_id;
+
+pub use logging::init_logging;
+pub use middleware::{request_context, TraceRequests};
+pub use opentelemetry::{Context, KeyValue};
+pub use request_id::{request_id, RequestId, REQUEST_ID_HEADER};
+
+use exporter::JsonLinesExporter;
+
+const TRACER_NAME: &str = "whoknows";
+
+/// Keeps the tracer provider alive; `shutdown` flushes spans still queued for export.
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                log::warn!("Failed to flush traces on shutdown: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Sets up tracing for `service_name`, e.g. `whoknows-backend`. Call once, early.
+pub fn init(settings: &TracingSettings, service_name: &'static str) -> Result<Telemetry, String> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    if settings.exporter == TracingExporter::None {
+        return Ok(Telemetry { provider: None });
+    }
+
+    // The OTLP exporter's blocking HTTP client can't be created on an async runtime.
+    let settings = settings.clone();
+    let provider = std::thread::spawn(move || build_provider(&settings, service_name))
+        .join()
+        .map_err(|_| "Tracing setup panicked".to_string())??;
+    global::set_tracer_provider(provider.clone());
+    Ok(Telemetry {
+        provider: Some(provider),
+    })
+}
+
+fn build_provider(
+    settings: &TracingSettings,
+    service_name: &'static str,
+) -> Result<SdkTracerProvider, String> {
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            settings.sample_ratio,
+        ))));
+    let builder = match settings.exporter {
+        TracingExporter::Otlp => {
+            use opentelemetry_otlp::WithExportConfig;
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", settings.otlp_endpoint))
+                .build()
+                .map_err(|e| format!("Failed to set up the OTLP exporter: {}", e))?;
+            builder.with_batch_exporter(exporter)
+        }
+        TracingExporter::Stdout => builder.with_batch_exporter(JsonLinesExporter::stdout()),
+        TracingExporter::File => {
+            let path = settings
+                .file
+                .as_deref()
+                .ok_or("TRACING_FILE is required for the file exporter")?;
+            builder.with_batch_exporter(JsonLinesExporter::file(path)?)
+        }
+        TracingExporter::None => builder,
+    };
+    Ok(builder.build())
+}
+
+pub fn tracer() -> BoxedTracer {
+    global::tracer(TRACER_NAME)
+}
+
+/// Starts a span for a call to another service, as a child of `parent`.
+pub fn client_span(parent: &Context, name: String, attributes: Vec<KeyValue>) -> Context {
+    let tracer = tracer();
+    let span = tracer
+        .span_builder(name)
+        .with_kind(SpanKind::Client)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, parent);
+    parent.with_span(span)
+}
+
+/// Ends the span in `cx` with the HTTP status of its response; 5xx marks it failed.
+pub fn end_http_span(cx: &Context, status: u16) {
+    let span = cx.span();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        i64::from(status),
+    ));
+    if status >= 500 {
+        span.set_status(Status::error(format!("HTTP {}", status)));
+    }
+    span.end();
+}
+
+/// Ends the span in `cx` as failed, for calls that got no response at all.
+pub fn end_failed_span(cx: &Context, error: String) {
+    let span = cx.span();
+    span.set_status(Status::error(error));
+    span.end();
+}
+
+/// Headers carrying `cx` to the next service, i.e. `traceparent` and `tracestate`.
+pub fn inject(cx: &Context) -> Vec<(String, String)> {
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(cx, &mut headers));
+    headers.into_iter().collect()
+}
@@ -0,0 +1,47 @@
+// telemetry/src/logging.rs
+// --- Log Output ---
+// Installs the `log` logger for both services. In the JSON format every line is one
+// object carrying the request ID and trace ID of the request it was written for, so a
+// log collector can pick out one request, or jump from a trace to its log lines.
+use std::io::Write;
+
+use env_logger::fmt::Formatter;
+use log::Record;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::Context;
+use whoknows_config::LogFormat;
+
+use crate::request_id::current_request_id;
+
+/// Sets up logging with `filter` in `env_logger` syntax, e.g. `info`. Call once, first.
+pub fn init_logging(filter: &str, format: LogFormat, service_name: &'static str) {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(filter);
+    if format == LogFormat::Json {
+        builder.format(move |buf, record| write_json(buf, record, service_name));
+    }
+    builder.init();
+}
+
+fn write_json(
+    buf: &mut Formatter,
+    record: &Record<'_>,
+    service_name: &'static str,
+) -> std::io::Result<()> {
+    let mut line = serde_json::json!({
+        "timestamp": buf.timestamp_millis().to_string(),
+        "level": record.level().as_str(),
+        "service": service_name,
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Some(request_id) = current_request_id() {
+        line["request_id"] = request_id.into();
+    }
+    let cx = Context::current();
+    let span_context = cx.span().span_context().clone();
+    if span_context.is_valid() {
+        line["trace_id"] = span_context.trace_id().to_string().into();
+    }
+    writeln!(buf, "{}", line)
+}
@@ -0,0 +1,116 @@
+// telemetry/src/middleware.rs
+// --- Request Tracing Middleware ---
+// Opens a server span for every request, continuing the trace from an incoming
+// `traceparent` header when there is one. The span's context is kept in the request
+// extensions and is current while the rest of the service runs, so handlers and
+// outgoing calls nest under it.
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::HeaderMap;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use futures::future::{self, LocalBoxFuture, Ready};
+use opentelemetry::context::FutureExt;
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{SpanKind, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+
+use crate::{end_http_span, tracer};
+
+/// The request's server span, stored in its extensions.
+#[derive(Clone)]
+struct RequestContext(Context);
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Context of the request's server span, to parent spans started on its behalf.
+pub fn request_context(req: &HttpRequest) -> Context {
+    req.extensions()
+        .get::<RequestContext>()
+        .map(|cx| cx.0.clone())
+        .unwrap_or_else(Context::current)
+}
+
+pub struct TraceRequests;
+
+impl<S, B> Transform<S, ServiceRequest> for TraceRequests
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TraceRequestsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(TraceRequestsMiddleware { service })
+    }
+}
+
+pub struct TraceRequestsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for TraceRequestsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(req.headers()))
+        });
+        let method = req.method().to_string();
+        let tracer = tracer();
+        // Renamed after routing, once the route pattern is known.
+        let span = tracer
+            .span_builder(method.clone())
+            .with_kind(SpanKind::Server)
+            .with_attributes(vec![
+                KeyValue::new("http.request.method", method.clone()),
+                KeyValue::new("url.path", req.path().to_string()),
+            ])
+            .start_with_context(&tracer, &parent);
+        let cx = parent.with_span(span);
+        req.extensions_mut().insert(RequestContext(cx.clone()));
+
+        let fut = {
+            let _attached = cx.clone().attach();
+            self.service.call(req).with_context(cx.clone())
+        };
+        Box::pin(async move {
+            let result = fut.await;
+            let span = cx.span();
+            match &result {
+                Ok(res) => {
+                    if let Some(route) = res.request().match_pattern() {
+                        span.update_name(format!("{} {}", method, route));
+                        span.set_attribute(KeyValue::new("http.route", route));
+                    }
+                    end_http_span(&cx, res.status().as_u16());
+                }
+                Err(e) => end_http_span(&cx, e.as_response_error().status_code().as_u16()),
+            }
+            result
+        })
+    }
+}
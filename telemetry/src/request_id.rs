@@ -0,0 +1,172 @@
+// telemetry/src/request_id.rs
+// --- Request ID Middleware ---
+// Gives every request an ID: the caller's `X-Request-Id` when it looks sane, a random
+// one otherwise. The ID is returned in the response, kept in the request extensions
+// for calls made on the request's behalf, and is current while the request is handled,
+// so `logging` adds it to every line. The middleware writes the access log line itself,
+// since actix's `Logger` only logs once the response body is gone and the ID with it.
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use futures::future::{self, LocalBoxFuture, Ready};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest incoming ID that is kept; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<str>>> = const { RefCell::new(None) };
+}
+
+/// The ID of the request being handled on this thread, if any.
+pub(crate) fn current_request_id() -> Option<String> {
+    CURRENT.with(|current| current.borrow().as_deref().map(str::to_string))
+}
+
+#[derive(Clone)]
+struct RequestIdValue(Rc<str>);
+
+/// The request's ID, to pass on to the services it calls.
+pub fn request_id(req: &HttpRequest) -> Option<String> {
+    req.extensions()
+        .get::<RequestIdValue>()
+        .map(|id| id.0.to_string())
+}
+
+// Only IDs that can't break a log line or a header are taken over from the caller.
+fn accepted(value: &HeaderValue) -> Option<&str> {
+    let id = value.to_str().ok()?;
+    let valid = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b));
+    valid.then_some(id)
+}
+
+fn generate() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Runs `f` with `id` as the current request's ID.
+fn scoped<T>(id: &Rc<str>, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|current| current.replace(Some(id.clone())));
+    let result = f();
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Polls the inner future with the request's ID current.
+struct WithRequestId<F> {
+    id: Rc<str>,
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for WithRequestId<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let id = self.id.clone();
+        scoped(&id, || self.inner.as_mut().poll(cx))
+    }
+}
+
+fn log_access(peer: Option<&str>, request_line: &str, status: StatusCode, started: Instant) {
+    log::info!(
+        target: "access",
+        "{} \"{}\" {} {:.3}ms",
+        peer.unwrap_or("-"),
+        request_line,
+        status.as_u16(),
+        started.elapsed().as_secs_f64() * 1000.0
+    );
+}
+
+pub struct RequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(RequestIdMiddleware { service })
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id: Rc<str> = match req.headers().get(&REQUEST_ID_HEADER).and_then(accepted) {
+            Some(id) => id.into(),
+            None => generate().into(),
+        };
+        req.extensions_mut().insert(RequestIdValue(id.clone()));
+
+        let started = Instant::now();
+        let peer = req.peer_addr().map(|addr| addr.ip().to_string());
+        let request_line = format!("{} {}", req.method(), req.path());
+
+        let fut = scoped(&id, || self.service.call(req));
+        Box::pin(WithRequestId {
+            id: id.clone(),
+            inner: Box::pin(async move {
+                let result = fut.await;
+                let status = match &result {
+                    Ok(res) => res.status(),
+                    Err(e) => e.as_response_error().status_code(),
+                };
+                log_access(peer.as_deref(), &request_line, status, started);
+                let value = HeaderValue::from_str(&id).ok();
+                match result {
+                    Ok(mut res) => {
+                        if let Some(value) = value {
+                            res.headers_mut().insert(REQUEST_ID_HEADER, value);
+                        }
+                        Ok(res)
+                    }
+                    // Errors are turned into responses further out; build it here instead,
+                    // so it carries the ID too.
+                    Err(e) => {
+                        let mut res = e.error_response();
+                        if let Some(value) = value {
+                            res.headers_mut().insert(REQUEST_ID_HEADER, value);
+                        }
+                        Err(InternalError::from_response(e, res).into())
+                    }
+                }
+            }),
+        })
+    }
+}